@@ -0,0 +1,363 @@
+use crate::{
+    is_promotion, Board, BoardSpot, Color, GameState, Movement, Piece, PieceType, Position,
+};
+
+// Squares are numbered line * 8 + column, so a1 is bit 0, h1 is bit 7 and h8 is bit 63
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+const FILE_H: u64 = FILE_A << 7;
+
+const KNIGHT_OFFSETS: [[i32; 2]; 8] = [
+    [2, 1],
+    [2, -1],
+    [1, 2],
+    [1, -2],
+    [-2, 1],
+    [-2, -1],
+    [-1, 2],
+    [-1, -2],
+];
+const KING_OFFSETS: [[i32; 2]; 8] = [
+    [1, 1],
+    [1, -1],
+    [-1, 1],
+    [-1, -1],
+    [0, 1],
+    [0, -1],
+    [1, 0],
+    [-1, 0],
+];
+
+// Sliding directions as [line, column] steps; the first four increase the square number
+const NORTH: usize = 0;
+const EAST: usize = 1;
+const NORTH_EAST: usize = 2;
+const NORTH_WEST: usize = 3;
+const SOUTH: usize = 4;
+const WEST: usize = 5;
+const SOUTH_WEST: usize = 6;
+const SOUTH_EAST: usize = 7;
+const DIRECTIONS: [[i32; 2]; 8] = [
+    [1, 0],
+    [0, 1],
+    [1, 1],
+    [1, -1],
+    [-1, 0],
+    [0, -1],
+    [-1, -1],
+    [-1, 1],
+];
+
+const fn leaper_attacks(offsets: &[[i32; 2]; 8]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut index = 0;
+        while index < 8 {
+            let line = (square / 8) as i32 + offsets[index][0];
+            let column = (square % 8) as i32 + offsets[index][1];
+            if line >= 0 && line < 8 && column >= 0 && column < 8 {
+                table[square] |= 1 << (line * 8 + column);
+            }
+            index += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+// Every square a slider reaches from each square on an empty board, one table per direction
+const fn rays() -> [[u64; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut line = (square / 8) as i32 + DIRECTIONS[direction][0];
+            let mut column = (square % 8) as i32 + DIRECTIONS[direction][1];
+            while line >= 0 && line < 8 && column >= 0 && column < 8 {
+                table[direction][square] |= 1 << (line * 8 + column);
+                line += DIRECTIONS[direction][0];
+                column += DIRECTIONS[direction][1];
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+const fn pawn_attacks() -> [[u64; 64]; 2] {
+    let mut table = [[0; 64]; 2];
+    let mut square = 0;
+    while square < 64 {
+        let bit: u64 = 1 << square;
+        table[Color::WHITE as usize][square] = ((bit << 7) & !FILE_H) | ((bit << 9) & !FILE_A);
+        table[Color::BLACK as usize][square] = ((bit >> 9) & !FILE_H) | ((bit >> 7) & !FILE_A);
+        square += 1;
+    }
+    table
+}
+
+const KNIGHT_ATTACKS: [u64; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+const KING_ATTACKS: [u64; 64] = leaper_attacks(&KING_OFFSETS);
+const PAWN_ATTACKS: [[u64; 64]; 2] = pawn_attacks();
+const RAYS: [[u64; 64]; 8] = rays();
+
+// The ray stops at the first blocker, which is included so captures come out of the same mask
+fn ray_attacks(square: usize, occupied: u64, direction: usize) -> u64 {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let blocker = if direction < SOUTH {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    ray ^ RAYS[direction][blocker as usize]
+}
+
+fn bishop_attacks(square: usize, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, NORTH_EAST)
+        | ray_attacks(square, occupied, NORTH_WEST)
+        | ray_attacks(square, occupied, SOUTH_EAST)
+        | ray_attacks(square, occupied, SOUTH_WEST)
+}
+
+fn rook_attacks(square: usize, occupied: u64) -> u64 {
+    ray_attacks(square, occupied, NORTH)
+        | ray_attacks(square, occupied, EAST)
+        | ray_attacks(square, occupied, SOUTH)
+        | ray_attacks(square, occupied, WEST)
+}
+
+pub fn square_index(position: &Position) -> usize {
+    position.line * 8 + position.column
+}
+
+fn square_position(square: usize) -> Position {
+    Position {
+        line: square / 8,
+        column: square % 8,
+    }
+}
+
+// Iterates over the squares of a bitboard, lowest first
+fn squares(mut bits: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let square = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        Some(square)
+    })
+}
+
+// One bitboard per color and piece type, kept alongside the array board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bitboards {
+    pieces: [[u64; 6]; 2],
+    colors: [u64; 2],
+}
+
+impl Bitboards {
+    pub fn from_board(board: &Board) -> Self {
+        let mut bitboards = Bitboards {
+            pieces: [[0; 6]; 2],
+            colors: [0; 2],
+        };
+        for (line, spots) in board.iter().enumerate() {
+            for (column, spot) in spots.iter().enumerate() {
+                bitboards.set(line * 8 + column, *spot);
+            }
+        }
+        bitboards
+    }
+
+    fn clear(&mut self, square: usize) {
+        let mask = !(1 << square);
+        for color in 0..2 {
+            self.colors[color] &= mask;
+            for piece_type in 0..6 {
+                self.pieces[color][piece_type] &= mask;
+            }
+        }
+    }
+
+    // Replaces whatever stood on the square, so repeating it for the same square is harmless
+    pub fn set(&mut self, square: usize, spot: BoardSpot) {
+        self.clear(square);
+        if let BoardSpot::Piece(piece) = spot {
+            self.pieces[piece.color as usize][piece.piece_type as usize] |= 1 << square;
+            self.colors[piece.color as usize] |= 1 << square;
+        }
+    }
+
+    fn occupied(&self) -> u64 {
+        self.colors[0] | self.colors[1]
+    }
+
+    fn piece_bits(&self, color: Color, piece_type: PieceType) -> u64 {
+        self.pieces[color as usize][piece_type as usize]
+    }
+
+    pub fn is_attacked(&self, square: usize, by: Color) -> bool {
+        let occupied = self.occupied();
+        let queens = self.piece_bits(by, PieceType::QUEEN);
+        // A pawn of the attacking color attacks the square if a pawn of the other color
+        // standing on it would attack the pawn back
+        PAWN_ATTACKS[by.reverse() as usize][square] & self.piece_bits(by, PieceType::PAWN) != 0
+            || KNIGHT_ATTACKS[square] & self.piece_bits(by, PieceType::KNIGHT) != 0
+            || KING_ATTACKS[square] & self.piece_bits(by, PieceType::KING) != 0
+            || bishop_attacks(square, occupied) & (self.piece_bits(by, PieceType::BISHOP) | queens)
+                != 0
+            || rook_attacks(square, occupied) & (self.piece_bits(by, PieceType::ROOK) | queens) != 0
+    }
+
+    pub fn is_in_check(&self, color: Color) -> bool {
+        let king = self.piece_bits(color, PieceType::KING);
+        king != 0 && self.is_attacked(king.trailing_zeros() as usize, color.reverse())
+    }
+
+    fn attacks(&self, piece: &Piece, square: usize) -> u64 {
+        let occupied = self.occupied();
+        match piece.piece_type {
+            PieceType::PAWN => PAWN_ATTACKS[piece.color as usize][square],
+            PieceType::KNIGHT => KNIGHT_ATTACKS[square],
+            PieceType::BISHOP => bishop_attacks(square, occupied),
+            PieceType::ROOK => rook_attacks(square, occupied),
+            PieceType::QUEEN => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+            PieceType::KING => KING_ATTACKS[square],
+        }
+    }
+
+    // Plays a non-castling movement on a copy and looks at the mover's king afterwards
+    fn leaves_king_safe(&self, piece: &Piece, from: usize, to: usize, captured: usize) -> bool {
+        let mut after = *self;
+        after.clear(captured);
+        after.clear(from);
+        after.set(to, BoardSpot::Piece(*piece));
+        !after.is_in_check(piece.color)
+    }
+}
+
+fn push_movement(moves: &mut Vec<Movement>, piece: &Piece, from: usize, to: usize) {
+    let starting_position = square_position(from);
+    let destination_position = square_position(to);
+    if is_promotion(piece, &destination_position) {
+        for promotion in [
+            PieceType::QUEEN,
+            PieceType::ROOK,
+            PieceType::BISHOP,
+            PieceType::KNIGHT,
+        ] {
+            moves.push(Movement {
+                starting_position,
+                destination_position,
+                promotion: Some(promotion),
+            });
+        }
+    } else {
+        moves.push(Movement {
+            starting_position,
+            destination_position,
+            promotion: None,
+        });
+    }
+}
+
+fn castling_movements(state: &GameState, moves: &mut Vec<Movement>) {
+    let bitboards = &state.bitboards;
+    let color = state.turn;
+    let line = match color {
+        Color::WHITE => 0,
+        Color::BLACK => 7,
+    };
+    let king_square = line * 8 + 4;
+    if bitboards.piece_bits(color, PieceType::KING) & (1 << king_square) == 0
+        || bitboards.is_in_check(color)
+    {
+        return;
+    }
+
+    // (has right, rook column, columns that must be empty, column the king passes through, destination)
+    let sides = [
+        (state.castling_rights.kingside(color), 7, 0b0110_0000, 5, 6),
+        (state.castling_rights.queenside(color), 0, 0b0000_1110, 3, 2),
+    ];
+    for (has_right, rook_column, empty_columns, passing_column, destination_column) in sides {
+        let rook = bitboards.piece_bits(color, PieceType::ROOK) & (1 << (line * 8 + rook_column));
+        if !has_right || rook == 0 || bitboards.occupied() & (empty_columns << (line * 8)) != 0 {
+            continue;
+        }
+        // Nothing attacking these squares can be uncovered by the king and rook swapping places
+        if bitboards.is_attacked(line * 8 + passing_column, color.reverse())
+            || bitboards.is_attacked(line * 8 + destination_column, color.reverse())
+        {
+            continue;
+        }
+        moves.push(Movement {
+            starting_position: square_position(king_square),
+            destination_position: square_position(line * 8 + destination_column),
+            promotion: None,
+        });
+    }
+}
+
+// Every legal movement of the side to move, the same set the array generator produces
+pub fn legal_moves(state: &GameState) -> Vec<Movement> {
+    let bitboards = &state.bitboards;
+    let color = state.turn;
+    let own = bitboards.colors[color as usize];
+    let enemy = bitboards.colors[color.reverse() as usize];
+    let empty = !bitboards.occupied();
+    let en_passant = state
+        .en_passant
+        .map_or(0, |position| 1 << square_index(&position));
+
+    let mut moves: Vec<Movement> = vec![];
+    for piece_type in [
+        PieceType::PAWN,
+        PieceType::KNIGHT,
+        PieceType::BISHOP,
+        PieceType::ROOK,
+        PieceType::QUEEN,
+        PieceType::KING,
+    ] {
+        let piece = Piece { piece_type, color };
+        for from in squares(bitboards.piece_bits(color, piece_type)) {
+            let targets = if piece_type == PieceType::PAWN {
+                let (single, double, start_line) = match color {
+                    Color::WHITE => (from + 8, from + 16, 1),
+                    Color::BLACK => (from.wrapping_sub(8), from.wrapping_sub(16), 6),
+                };
+                let mut pushes = 0;
+                if single < 64 && empty & (1 << single) != 0 {
+                    pushes |= 1 << single;
+                    if from / 8 == start_line && empty & (1 << double) != 0 {
+                        pushes |= 1 << double;
+                    }
+                }
+                pushes | (bitboards.attacks(&piece, from) & (enemy | en_passant))
+            } else {
+                bitboards.attacks(&piece, from) & !own
+            };
+
+            for to in squares(targets) {
+                // Only an en passant capture takes a pawn away from somewhere other than the destination
+                let captured = if piece_type == PieceType::PAWN && en_passant & (1 << to) != 0 {
+                    (from / 8) * 8 + to % 8
+                } else {
+                    to
+                };
+                if bitboards.leaves_king_safe(&piece, from, to, captured) {
+                    push_movement(&mut moves, &piece, from, to);
+                }
+            }
+        }
+    }
+    castling_movements(state, &mut moves);
+    moves
+}
@@ -0,0 +1,362 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::Color;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bonus {
+    NONE,
+    INCREMENT(Duration),
+    // The clock waits this long each move before it starts running
+    SIMPLEDELAY(Duration),
+    // The time used is given back afterwards, up to this much
+    BRONSTEIN(Duration),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stage {
+    // Moves to play before the next stage starts, None for the rest of the game
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TimeControlError {
+    EMPTY,
+    INVALIDSTAGE(String),
+    MISSINGMOVES(String),
+}
+
+impl fmt::Display for TimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeControlError::EMPTY => write!(f, "The time control is empty"),
+            TimeControlError::INVALIDSTAGE(stage) => write!(
+                f,
+                "Invalid time control stage '{stage}', expected [moves/]minutes[+increment|d<delay>|b<delay>] (e.g. 5+3, 40/90+30:30+30 or 15d5)"
+            ),
+            TimeControlError::MISSINGMOVES(stage) => write!(
+                f,
+                "Stage '{stage}' must say how many moves it lasts, only the last stage may omit it"
+            ),
+        }
+    }
+}
+
+// Negative, non finite and too large values are all rejected by try_from_secs_f64
+fn parse_seconds(field: &str) -> Option<Duration> {
+    let seconds = field.parse::<f64>().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+fn parse_stage(field: &str) -> Option<Stage> {
+    let (moves, rest) = match field.split_once('/') {
+        Some((moves, rest)) => (
+            Some(moves.parse::<u32>().ok().filter(|moves| *moves > 0)?),
+            rest,
+        ),
+        None => (None, field),
+    };
+    let (minutes, bonus) = match rest.find(['+', 'd', 'b']) {
+        Some(index) => {
+            let seconds = parse_seconds(&rest[index + 1..])?;
+            let bonus = match &rest[index..index + 1] {
+                "+" => Bonus::INCREMENT(seconds),
+                "d" => Bonus::SIMPLEDELAY(seconds),
+                _ => Bonus::BRONSTEIN(seconds),
+            };
+            (&rest[..index], bonus)
+        }
+        None => (rest, Bonus::NONE),
+    };
+    let time = parse_seconds(minutes)?.checked_mul(60)?;
+    if time.is_zero() {
+        return None;
+    }
+    Some(Stage { moves, time, bonus })
+}
+
+// Stages are separated by ':', e.g. "40/90+30:30+30" is 90 minutes for 40 moves then 30 for the rest,
+// with 30 seconds added every move
+pub fn parse_time_control(text: &str) -> Result<Vec<Stage>, TimeControlError> {
+    if text.trim().is_empty() {
+        return Err(TimeControlError::EMPTY);
+    }
+    let fields: Vec<&str> = text.trim().split(':').collect();
+    let mut stages = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        let stage =
+            parse_stage(field).ok_or_else(|| TimeControlError::INVALIDSTAGE(field.to_string()))?;
+        if stage.moves.is_none() && index + 1 < fields.len() {
+            return Err(TimeControlError::MISSINGMOVES(field.to_string()));
+        }
+        stages.push(stage);
+    }
+    Ok(stages)
+}
+
+// Formats as m:ss, or h:mm:ss for long games, with tenths once under ten seconds
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else if seconds >= 10 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("0:{:02}.{}", seconds, duration.subsec_millis() / 100)
+    }
+}
+
+// Everything press changes, saved before every movement so undo can rewind the stages too
+#[derive(Clone, Copy)]
+struct Counters {
+    remaining: [Duration; 2],
+    stage: [usize; 2],
+    moves_in_stage: [u32; 2],
+}
+
+pub struct Clock {
+    stages: Vec<Stage>,
+    counters: Counters,
+    history: Vec<Counters>,
+    // Counters taken back by take_back, put back by replay until a new movement is pressed
+    undone: Vec<Counters>,
+    turn_started: Instant,
+}
+
+impl Clock {
+    pub fn new(stages: Vec<Stage>) -> Self {
+        let time = stages[0].time;
+        Clock {
+            stages,
+            counters: Counters {
+                remaining: [time; 2],
+                stage: [0; 2],
+                moves_in_stage: [0; 2],
+            },
+            history: vec![],
+            undone: vec![],
+            turn_started: Instant::now(),
+        }
+    }
+
+    fn current_stage(&self, color: Color) -> &Stage {
+        &self.stages[self.counters.stage[color as usize]]
+    }
+
+    // The part of the turn so far that counts against the clock, a Bronstein delay is only given
+    // back once the movement is made
+    fn charged(&self, color: Color, elapsed: Duration) -> Duration {
+        match self.current_stage(color).bonus {
+            Bonus::SIMPLEDELAY(delay) => elapsed.saturating_sub(delay),
+            Bonus::NONE | Bonus::INCREMENT(_) | Bonus::BRONSTEIN(_) => elapsed,
+        }
+    }
+
+    pub fn remaining(&self, color: Color, turn: Color) -> Duration {
+        let remaining = self.counters.remaining[color as usize];
+        if color == turn {
+            remaining.saturating_sub(self.charged(color, self.turn_started.elapsed()))
+        } else {
+            remaining
+        }
+    }
+
+    pub fn is_flagged(&self, turn: Color) -> bool {
+        self.remaining(turn, turn).is_zero()
+    }
+
+    pub fn bonus_time(&self, color: Color) -> Option<Duration> {
+        match self.current_stage(color).bonus {
+            Bonus::NONE => None,
+            Bonus::INCREMENT(time) | Bonus::SIMPLEDELAY(time) | Bonus::BRONSTEIN(time) => {
+                Some(time)
+            }
+        }
+    }
+
+    pub fn moves_to_go(&self, color: Color) -> Option<u32> {
+        self.current_stage(color)
+            .moves
+            .map(|moves| moves - self.counters.moves_in_stage[color as usize])
+    }
+
+    // Stops the mover's clock after a movement and starts the opponent's, returning false if
+    // the mover ran out of time before finishing it
+    pub fn press(&mut self, mover: Color) -> bool {
+        let index = mover as usize;
+        let elapsed = self.turn_started.elapsed();
+        let charged = self.charged(mover, elapsed);
+        if charged >= self.counters.remaining[index] {
+            self.counters.remaining[index] = Duration::ZERO;
+            return false;
+        }
+        self.history.push(self.counters);
+        self.undone.clear();
+        let counters = &mut self.counters;
+        counters.remaining[index] -= charged;
+        match self.stages[counters.stage[index]].bonus {
+            Bonus::INCREMENT(increment) => {
+                counters.remaining[index] = counters.remaining[index].saturating_add(increment)
+            }
+            Bonus::BRONSTEIN(delay) => {
+                counters.remaining[index] =
+                    counters.remaining[index].saturating_add(elapsed.min(delay))
+            }
+            Bonus::NONE | Bonus::SIMPLEDELAY(_) => {}
+        }
+
+        // Once the moves of a stage are played its time is added, the last stage repeating if it has a move count
+        counters.moves_in_stage[index] += 1;
+        if Some(counters.moves_in_stage[index]) == self.stages[counters.stage[index]].moves {
+            counters.moves_in_stage[index] = 0;
+            counters.stage[index] = (counters.stage[index] + 1).min(self.stages.len() - 1);
+            counters.remaining[index] =
+                counters.remaining[index].saturating_add(self.stages[counters.stage[index]].time);
+        }
+        self.turn_started = Instant::now();
+        true
+    }
+
+    // Puts the clock back to before the last pressed movement, for undo
+    pub fn take_back(&mut self) {
+        if let Some(previous) = self.history.pop() {
+            self.undone.push(self.counters);
+            self.counters = previous;
+        }
+        self.turn_started = Instant::now();
+    }
+
+    // Brings back the counters of a movement taken back, for redo
+    pub fn replay(&mut self) {
+        if let Some(next) = self.undone.pop() {
+            self.history.push(self.counters);
+            self.counters = next;
+        }
+        self.turn_started = Instant::now();
+    }
+
+    pub fn show(&self, turn: Color) {
+        let describe = |color: Color| {
+            let marker = if color == turn { "*" } else { " " };
+            format!(
+                "{marker}{} {}",
+                color.name(),
+                format_duration(self.remaining(color, turn))
+            )
+        };
+        println!("{}  {}", describe(Color::WHITE), describe(Color::BLACK));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minutes(minutes: u64) -> Duration {
+        Duration::from_secs(minutes * 60)
+    }
+
+    #[test]
+    fn time_controls_are_parsed() {
+        assert_eq!(
+            parse_time_control("5+3"),
+            Ok(vec![Stage {
+                moves: None,
+                time: minutes(5),
+                bonus: Bonus::INCREMENT(Duration::from_secs(3)),
+            }])
+        );
+        assert_eq!(
+            parse_time_control("40/90+30:30+30"),
+            Ok(vec![
+                Stage {
+                    moves: Some(40),
+                    time: minutes(90),
+                    bonus: Bonus::INCREMENT(Duration::from_secs(30)),
+                },
+                Stage {
+                    moves: None,
+                    time: minutes(30),
+                    bonus: Bonus::INCREMENT(Duration::from_secs(30)),
+                },
+            ])
+        );
+        assert_eq!(
+            parse_time_control("15d5"),
+            Ok(vec![Stage {
+                moves: None,
+                time: minutes(15),
+                bonus: Bonus::SIMPLEDELAY(Duration::from_secs(5)),
+            }])
+        );
+        assert_eq!(
+            parse_time_control("15b5"),
+            Ok(vec![Stage {
+                moves: None,
+                time: minutes(15),
+                bonus: Bonus::BRONSTEIN(Duration::from_secs(5)),
+            }])
+        );
+        assert_eq!(
+            parse_time_control(" 0.5 "),
+            Ok(vec![Stage {
+                moves: None,
+                time: Duration::from_secs(30),
+                bonus: Bonus::NONE,
+            }])
+        );
+    }
+
+    #[test]
+    fn invalid_time_controls_are_rejected() {
+        assert_eq!(parse_time_control(""), Err(TimeControlError::EMPTY));
+        assert_eq!(parse_time_control("  "), Err(TimeControlError::EMPTY));
+        for stage in [
+            "abc", "0", "-5", "5+", "5+x", "5+-1", "0/5", "x/5", "5x3", "1e18", "5+1e300", "inf",
+            "NaN",
+        ] {
+            assert_eq!(
+                parse_time_control(stage),
+                Err(TimeControlError::INVALIDSTAGE(stage.to_string())),
+                "{stage}"
+            );
+        }
+        assert_eq!(
+            parse_time_control("90:30"),
+            Err(TimeControlError::MISSINGMOVES(String::from("90")))
+        );
+    }
+
+    #[test]
+    fn take_back_rewinds_across_a_stage() {
+        let mut clock = Clock::new(parse_time_control("2/10:5").unwrap());
+        assert_eq!(clock.moves_to_go(Color::WHITE), Some(2));
+        for mover in [Color::WHITE, Color::BLACK, Color::WHITE] {
+            assert!(clock.press(mover));
+        }
+        assert_eq!(clock.moves_to_go(Color::WHITE), None);
+        assert!(clock.remaining(Color::WHITE, Color::BLACK) > minutes(14));
+
+        clock.take_back();
+        assert_eq!(clock.moves_to_go(Color::WHITE), Some(1));
+        assert!(clock.remaining(Color::WHITE, Color::BLACK) <= minutes(10));
+
+        clock.replay();
+        assert_eq!(clock.moves_to_go(Color::WHITE), None);
+        assert!(clock.remaining(Color::WHITE, Color::BLACK) > minutes(14));
+
+        // A new movement after take_back starts a new line, there is nothing left to replay
+        clock.take_back();
+        assert!(clock.press(Color::WHITE));
+        let remaining = clock.remaining(Color::WHITE, Color::BLACK);
+        clock.replay();
+        assert_eq!(clock.remaining(Color::WHITE, Color::BLACK), remaining);
+    }
+}
@@ -0,0 +1,326 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{BoardSpot, Color, GameState, Movement, Piece, PieceType};
+
+const MATE_SCORE: i32 = 100_000;
+const MAX_DEPTH: u32 = 64;
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub movetime: Option<Duration>,
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    pub infinite: bool,
+}
+
+impl SearchLimits {
+    // How long the side to move may think, None meaning until stopped or the depth is reached
+    pub fn time_budget(&self, turn: Color) -> Option<Duration> {
+        if self.infinite {
+            return None;
+        }
+        if self.movetime.is_some() {
+            return self.movetime;
+        }
+        let (time, increment) = match turn {
+            Color::WHITE => (self.white_time, self.white_increment),
+            Color::BLACK => (self.black_time, self.black_increment),
+        };
+        let time = time?;
+        let increment = increment.unwrap_or_default();
+        let moves_to_go = self.moves_to_go.unwrap_or(30).max(1);
+        // Never plan to spend more than half of what is left on the clock
+        Some(
+            (time / moves_to_go)
+                .saturating_add(increment / 2)
+                .min(time / 2),
+        )
+    }
+}
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::PAWN => 100,
+        PieceType::KNIGHT => 320,
+        PieceType::BISHOP => 330,
+        PieceType::ROOK => 500,
+        PieceType::QUEEN => 900,
+        PieceType::KING => 0,
+    }
+}
+
+// Piece-square tables from White's point of view, listed from rank 8 down to rank 1
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+fn square_value(piece: &Piece, line: usize, column: usize) -> i32 {
+    let table = match piece.piece_type {
+        PieceType::PAWN => &PAWN_TABLE,
+        PieceType::KNIGHT => &KNIGHT_TABLE,
+        PieceType::BISHOP => &BISHOP_TABLE,
+        PieceType::ROOK => &ROOK_TABLE,
+        PieceType::QUEEN => &QUEEN_TABLE,
+        PieceType::KING => &KING_TABLE,
+    };
+    // The tables start at rank 8, so White reads them upside down and Black reads them as listed
+    let row = match piece.color {
+        Color::WHITE => 7 - line,
+        Color::BLACK => line,
+    };
+    table[row * 8 + column]
+}
+
+// Material plus piece-square bonuses, from the point of view of the side to move
+pub fn evaluate(state: &GameState) -> i32 {
+    let mut score = 0;
+    for (line, spots) in state.board.iter().enumerate() {
+        for (column, spot) in spots.iter().enumerate() {
+            if let BoardSpot::Piece(piece) = spot {
+                let value = piece_value(piece.piece_type) + square_value(piece, line, column);
+                if piece.color == state.turn {
+                    score += value;
+                } else {
+                    score -= value;
+                }
+            }
+        }
+    }
+    score
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_SCORE - MAX_DEPTH as i32
+}
+
+// Full moves until mate, negative when the side to move is the one getting mated
+pub fn mate_distance(score: i32) -> i32 {
+    let moves = (MATE_SCORE - score.abs() + 1) / 2;
+    if score > 0 {
+        moves
+    } else {
+        -moves
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub best_move: Movement,
+}
+
+struct Searcher<'a> {
+    stop: &'a AtomicBool,
+    started: Instant,
+    time_budget: Option<Duration>,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl Searcher<'_> {
+    fn should_stop(&mut self) -> bool {
+        if !self.aborted && self.nodes.is_multiple_of(64) {
+            let out_of_time = self
+                .time_budget
+                .is_some_and(|budget| self.started.elapsed() >= budget);
+            self.aborted = out_of_time || self.stop.load(Ordering::Relaxed);
+        }
+        self.aborted
+    }
+
+    fn negamax(
+        &mut self,
+        state: &mut GameState,
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+        ply: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+
+        // Leaves skip move generation, so mates and stalemates are seen one ply later
+        if depth == 0 {
+            return evaluate(state);
+        }
+        let mut moves = state.legal_moves();
+        if moves.is_empty() {
+            return if state.is_check() {
+                -MATE_SCORE + ply
+            } else {
+                0
+            };
+        }
+
+        order_moves(state, &mut moves, None);
+        for movement in moves {
+            state.make_move(movement);
+            let score = -self.negamax(state, depth - 1, -beta, -alpha, ply + 1);
+            state.unmake_move();
+            if self.aborted {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+// Captures of valuable pieces by cheap ones and promotions first, then the rest
+fn order_moves(state: &GameState, moves: &mut [Movement], first: Option<Movement>) {
+    moves.sort_by_cached_key(|movement| {
+        if Some(*movement) == first {
+            return i32::MIN;
+        }
+        let start = movement.starting_position;
+        let destination = movement.destination_position;
+        let attacker = match state.board[start.line][start.column] {
+            BoardSpot::Piece(piece) => piece_value(piece.piece_type),
+            BoardSpot::BLANK => 0,
+        };
+        let victim = match state.board[destination.line][destination.column] {
+            BoardSpot::Piece(piece) => piece_value(piece.piece_type),
+            BoardSpot::BLANK => 0,
+        };
+        let promotion = movement.promotion.map_or(0, piece_value);
+        if victim > 0 || promotion > 0 {
+            -(victim * 10 - attacker + promotion * 10)
+        } else {
+            0
+        }
+    });
+}
+
+// Iterative deepening negamax with alpha-beta pruning, reporting every completed depth
+pub fn search(
+    state: &mut GameState,
+    limits: &SearchLimits,
+    stop: &AtomicBool,
+    on_iteration: &mut dyn FnMut(&SearchInfo),
+) -> Option<Movement> {
+    let mut searcher = Searcher {
+        stop,
+        started: Instant::now(),
+        time_budget: limits.time_budget(state.turn),
+        nodes: 0,
+        aborted: false,
+    };
+    let mut moves = state.legal_moves();
+    let mut best_move = *moves.first()?;
+
+    for depth in 1..=limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH) {
+        order_moves(state, &mut moves, Some(best_move));
+        let mut alpha = -MATE_SCORE - 1;
+        let mut iteration_best = best_move;
+        for movement in &moves {
+            state.make_move(*movement);
+            let score = -searcher.negamax(state, depth - 1, -MATE_SCORE - 1, -alpha, 1);
+            state.unmake_move();
+            if searcher.aborted {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                iteration_best = *movement;
+            }
+        }
+        if searcher.aborted {
+            break;
+        }
+
+        best_move = iteration_best;
+        on_iteration(&SearchInfo {
+            depth,
+            score: alpha,
+            nodes: searcher.nodes,
+            elapsed: searcher.started.elapsed(),
+            best_move,
+        });
+        // Nothing left to improve once a forced mate is found or only one move exists
+        if is_mate_score(alpha) || moves.len() == 1 {
+            break;
+        }
+    }
+    Some(best_move)
+}
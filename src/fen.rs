@@ -0,0 +1,395 @@
+use std::fmt;
+
+use crate::bitboard::Bitboards;
+use crate::zobrist;
+use crate::{
+    notation_to_position, verify_if_was_check, Board, BoardSpot, CastlingRights, Color, GameState,
+    Piece, PieceType,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    WRONGFIELDCOUNT(usize),
+    WRONGRANKCOUNT(usize),
+    WRONGRANKLENGTH { rank: usize },
+    INVALIDPIECE(char),
+    INVALIDSIDETOMOVE(String),
+    INVALIDCASTLING(String),
+    INVALIDENPASSANT(String),
+    INVALIDHALFMOVECLOCK(String),
+    INVALIDFULLMOVENUMBER(String),
+    MISSINGKING(Color),
+    OPPONENTINCHECK,
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::WRONGFIELDCOUNT(count) => {
+                write!(f, "Expected 4 or 6 space separated fields, found {count}")
+            }
+            FenError::WRONGRANKCOUNT(count) => {
+                write!(f, "Expected 8 ranks separated by '/', found {count}")
+            }
+            FenError::WRONGRANKLENGTH { rank } => {
+                write!(f, "Rank {rank} does not describe exactly 8 squares")
+            }
+            FenError::INVALIDPIECE(letter) => write!(f, "Invalid piece letter '{letter}'"),
+            FenError::INVALIDSIDETOMOVE(field) => {
+                write!(f, "Invalid side to move '{field}', expected 'w' or 'b'")
+            }
+            FenError::INVALIDCASTLING(field) => write!(f, "Invalid castling rights '{field}'"),
+            FenError::INVALIDENPASSANT(field) => write!(f, "Invalid en passant square '{field}'"),
+            FenError::INVALIDHALFMOVECLOCK(field) => write!(f, "Invalid halfmove clock '{field}'"),
+            FenError::INVALIDFULLMOVENUMBER(field) => {
+                write!(f, "Invalid fullmove number '{field}'")
+            }
+            FenError::MISSINGKING(Color::WHITE) => write!(f, "White must have exactly one king"),
+            FenError::MISSINGKING(Color::BLACK) => write!(f, "Black must have exactly one king"),
+            FenError::OPPONENTINCHECK => {
+                write!(f, "The side that is not to move cannot be in check")
+            }
+        }
+    }
+}
+
+pub fn piece_to_letter(piece: &Piece) -> char {
+    let letter = match piece.piece_type {
+        PieceType::PAWN => 'p',
+        PieceType::ROOK => 'r',
+        PieceType::KNIGHT => 'n',
+        PieceType::BISHOP => 'b',
+        PieceType::QUEEN => 'q',
+        PieceType::KING => 'k',
+    };
+    match piece.color {
+        Color::WHITE => letter.to_ascii_uppercase(),
+        Color::BLACK => letter,
+    }
+}
+
+pub fn letter_to_piece(letter: char) -> Option<Piece> {
+    let piece_type = match letter.to_ascii_lowercase() {
+        'p' => PieceType::PAWN,
+        'r' => PieceType::ROOK,
+        'n' => PieceType::KNIGHT,
+        'b' => PieceType::BISHOP,
+        'q' => PieceType::QUEEN,
+        'k' => PieceType::KING,
+        _ => return None,
+    };
+    let color = if letter.is_ascii_uppercase() {
+        Color::WHITE
+    } else {
+        Color::BLACK
+    };
+    Some(Piece { piece_type, color })
+}
+
+fn parse_placement(placement: &str) -> Result<Board, FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::WRONGRANKCOUNT(ranks.len()));
+    }
+
+    let mut board: Board = [[BoardSpot::BLANK; 8]; 8];
+    // FEN lists rank 8 first, while the board keeps rank 1 on line 0
+    for (index, rank) in ranks.iter().enumerate() {
+        let line = 7 - index;
+        let mut column = 0;
+        for letter in rank.chars() {
+            if let Some(empty_squares) = letter.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                column += empty_squares as usize;
+            } else {
+                let piece = letter_to_piece(letter).ok_or(FenError::INVALIDPIECE(letter))?;
+                if column >= 8 {
+                    return Err(FenError::WRONGRANKLENGTH { rank: line + 1 });
+                }
+                board[line][column] = BoardSpot::Piece(piece);
+                column += 1;
+            }
+            if column > 8 {
+                return Err(FenError::WRONGRANKLENGTH { rank: line + 1 });
+            }
+        }
+        if column != 8 {
+            return Err(FenError::WRONGRANKLENGTH { rank: line + 1 });
+        }
+    }
+
+    for color in [Color::WHITE, Color::BLACK] {
+        let king = BoardSpot::Piece(Piece {
+            piece_type: PieceType::KING,
+            color,
+        });
+        let kings = board.iter().flatten().filter(|spot| **spot == king).count();
+        if kings != 1 {
+            return Err(FenError::MISSINGKING(color));
+        }
+    }
+    Ok(board)
+}
+
+fn parse_castling(field: &str) -> Result<CastlingRights, FenError> {
+    let mut castling_rights = CastlingRights {
+        white_kingside: false,
+        white_queenside: false,
+        black_kingside: false,
+        black_queenside: false,
+    };
+    if field == "-" {
+        return Ok(castling_rights);
+    }
+    for letter in field.chars() {
+        let right = match letter {
+            'K' => &mut castling_rights.white_kingside,
+            'Q' => &mut castling_rights.white_queenside,
+            'k' => &mut castling_rights.black_kingside,
+            'q' => &mut castling_rights.black_queenside,
+            _ => return Err(FenError::INVALIDCASTLING(field.to_string())),
+        };
+        if *right {
+            return Err(FenError::INVALIDCASTLING(field.to_string()));
+        }
+        *right = true;
+    }
+    Ok(castling_rights)
+}
+
+impl GameState {
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::WRONGFIELDCOUNT(fields.len()));
+        }
+
+        let board = parse_placement(fields[0])?;
+
+        let turn = match fields[1] {
+            "w" => Color::WHITE,
+            "b" => Color::BLACK,
+            field => return Err(FenError::INVALIDSIDETOMOVE(field.to_string())),
+        };
+
+        if verify_if_was_check(&board, turn.reverse()) {
+            return Err(FenError::OPPONENTINCHECK);
+        }
+
+        let castling_rights = parse_castling(fields[2])?;
+
+        let en_passant = match fields[3] {
+            "-" => None,
+            field => {
+                let position = notation_to_position(field)
+                    .ok_or_else(|| FenError::INVALIDENPASSANT(field.to_string()))?;
+                // The target square always sits behind a pawn that just made its double step,
+                // so it and the square the pawn left are empty and the pawn stands in front
+                let (expected_line, pawn_line, start_line) = match turn {
+                    Color::WHITE => (5, 4, 6),
+                    Color::BLACK => (2, 3, 1),
+                };
+                let pawn = BoardSpot::Piece(Piece {
+                    piece_type: PieceType::PAWN,
+                    color: turn.reverse(),
+                });
+                let column = position.column;
+                if position.line != expected_line
+                    || board[pawn_line][column] != pawn
+                    || board[expected_line][column] != BoardSpot::BLANK
+                    || board[start_line][column] != BoardSpot::BLANK
+                {
+                    return Err(FenError::INVALIDENPASSANT(field.to_string()));
+                }
+                Some(position)
+            }
+        };
+
+        let (halfmove_clock, fullmove_number) = if fields.len() == 6 {
+            let halfmove_clock = fields[4]
+                .parse::<u32>()
+                .map_err(|_| FenError::INVALIDHALFMOVECLOCK(fields[4].to_string()))?;
+            let fullmove_number = fields[5]
+                .parse::<u32>()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| FenError::INVALIDFULLMOVENUMBER(fields[5].to_string()))?;
+            (halfmove_clock, fullmove_number)
+        } else {
+            (0, 1)
+        };
+
+        let mut state = GameState {
+            board,
+            bitboards: Bitboards::from_board(&board),
+            hash: 0,
+            turn,
+            castling_rights,
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+            history: vec![],
+        };
+        state.hash = zobrist::hash(&state);
+        Ok(state)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut ranks: Vec<String> = vec![];
+        for line in self.board.iter().rev() {
+            let mut rank = String::new();
+            let mut empty_squares = 0;
+            for spot in line {
+                match spot {
+                    BoardSpot::Piece(piece) => {
+                        if empty_squares > 0 {
+                            rank.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        rank.push(piece_to_letter(piece));
+                    }
+                    BoardSpot::BLANK => empty_squares += 1,
+                }
+            }
+            if empty_squares > 0 {
+                rank.push_str(&empty_squares.to_string());
+            }
+            ranks.push(rank);
+        }
+
+        let turn = match self.turn {
+            Color::WHITE => "w",
+            Color::BLACK => "b",
+        };
+
+        let mut castling = String::new();
+        for (has_right, letter) in [
+            (self.castling_rights.white_kingside, 'K'),
+            (self.castling_rights.white_queenside, 'Q'),
+            (self.castling_rights.black_kingside, 'k'),
+            (self.castling_rights.black_queenside, 'q'),
+        ] {
+            if has_right {
+                castling.push(letter);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let en_passant = self
+            .en_passant
+            .map_or(String::from("-"), |position| position.to_notation());
+
+        format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            turn,
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fen_round_trips() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b Kq e3 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 12 40",
+        ] {
+            assert_eq!(GameState::from_fen(fen).unwrap().to_fen(), fen);
+        }
+        assert_eq!(
+            GameState::new().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        // The move counters are optional and default to a fresh game
+        let short = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+        assert_eq!(short.to_fen(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn invalid_fens_are_rejected() {
+        for (fen, expected) in [
+            ("4k3/8/8/8/8/8/8/4K3 w -", FenError::WRONGFIELDCOUNT(3)),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::WRONGRANKCOUNT(7)),
+            (
+                "4k4/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::WRONGRANKLENGTH { rank: 8 },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2 w - - 0 1",
+                FenError::WRONGRANKLENGTH { rank: 1 },
+            ),
+            ("4k3/8/8/8/8/8/8/4X3 w - - 0 1", FenError::INVALIDPIECE('X')),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::INVALIDSIDETOMOVE(String::from("x")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w KK - 0 1",
+                FenError::INVALIDCASTLING(String::from("KK")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w X - 0 1",
+                FenError::INVALIDCASTLING(String::from("X")),
+            ),
+            (
+                "4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1",
+                FenError::INVALIDENPASSANT(String::from("e3")),
+            ),
+            (
+                "4k3/8/8/3P4/8/8/8/4K3 w - e6 0 1",
+                FenError::INVALIDENPASSANT(String::from("e6")),
+            ),
+            (
+                "4k3/4p3/8/3Pp3/8/8/8/4K3 w - e6 0 1",
+                FenError::INVALIDENPASSANT(String::from("e6")),
+            ),
+            (
+                "4k3/8/4n3/3Pp3/8/8/8/4K3 w - e6 0 1",
+                FenError::INVALIDENPASSANT(String::from("e6")),
+            ),
+            (
+                "4k3/8/8/8/3Pp3/8/3P4/4K3 b - d3 0 1",
+                FenError::INVALIDENPASSANT(String::from("d3")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - z9 0 1",
+                FenError::INVALIDENPASSANT(String::from("z9")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - -1 1",
+                FenError::INVALIDHALFMOVECLOCK(String::from("-1")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+                FenError::INVALIDFULLMOVENUMBER(String::from("0")),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/8 w - - 0 1",
+                FenError::MISSINGKING(Color::WHITE),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                FenError::MISSINGKING(Color::WHITE),
+            ),
+            (
+                "8/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::MISSINGKING(Color::BLACK),
+            ),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OPPONENTINCHECK),
+        ] {
+            assert_eq!(GameState::from_fen(fen).err(), Some(expected), "{fen}");
+        }
+    }
+}
@@ -1,17 +1,44 @@
+#![allow(clippy::upper_case_acronyms)]
+
+mod bitboard;
+mod clock;
+mod engine;
+mod fen;
+mod perft;
+mod pgn;
+mod render;
+mod san;
+mod tui;
+mod uci;
+mod viewer;
+mod zobrist;
+
 use std::collections::HashMap;
+use std::env;
+use std::fmt;
 use std::io::{stdin, stdout, Write};
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use bitboard::Bitboards;
+use clock::Clock;
+use engine::SearchLimits;
+use render::{BoardView, Orientation, Style};
+
+use san::SanError;
 
 const DIAGONALS: [[isize; 2]; 4] = [[1, 1], [1, -1], [-1, 1], [-1, -1]];
 const SIDES: [[isize; 2]; 4] = [[0, 1], [0, -1], [1, 0], [-1, 0]];
 
 trait InfoArray {
-    fn unwrap_array(&self) -> Result<[Info; 2], &'static str>;
+    fn unwrap_array(self) -> Result<[Info; 2], MoveParseError>;
 }
 
-impl InfoArray for [Result<Info, &'static str>; 2] {
-    fn unwrap_array(&self) -> Result<[Info; 2], &'static str> {
-        match &self {
-            [Ok(a), Ok(b)] => Ok([*a, *b]),
+impl InfoArray for [Result<Info, MoveParseError>; 2] {
+    fn unwrap_array(self) -> Result<[Info; 2], MoveParseError> {
+        match self {
+            [Ok(a), Ok(b)] => Ok([a, b]),
             [Err(err), _] => Err(err),
             [_, Err(err)] => Err(err),
         }
@@ -47,6 +74,13 @@ impl Color {
             Color::BLACK => Color::WHITE,
         }
     }
+
+    fn name(&self) -> &'static str {
+        match &self {
+            Color::WHITE => "brancas",
+            Color::BLACK => "pretas",
+        }
+    }
 }
 
 enum Columns {
@@ -75,7 +109,7 @@ impl PartialEq for Position {
 }
 
 impl BoardSpot {
-    fn to_board_representation(&self) -> char {
+    fn to_board_representation(self) -> char {
         match self {
             BoardSpot::Piece(Piece {
                 piece_type: PieceType::PAWN,
@@ -138,12 +172,179 @@ struct Position {
     column: usize,
 }
 
+impl Position {
+    fn to_notation(self) -> String {
+        format!("{}{}", (b'a' + self.column as u8) as char, self.line + 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CastlingRights {
+    white_kingside: bool,
+    white_queenside: bool,
+    black_kingside: bool,
+    black_queenside: bool,
+}
+
+impl CastlingRights {
+    fn new() -> Self {
+        CastlingRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        }
+    }
+
+    fn kingside(&self, color: Color) -> bool {
+        match color {
+            Color::WHITE => self.white_kingside,
+            Color::BLACK => self.black_kingside,
+        }
+    }
+
+    fn queenside(&self, color: Color) -> bool {
+        match color {
+            Color::WHITE => self.white_queenside,
+            Color::BLACK => self.black_queenside,
+        }
+    }
+
+    // A right is lost as soon as anything leaves or lands on the king or rook home squares,
+    // which covers king moves, rook moves and rook captures
+    fn update(&mut self, starting_position: &Position, destination_position: &Position) {
+        for position in [starting_position, destination_position] {
+            match (position.line, position.column) {
+                (0, 0) => self.white_queenside = false,
+                (0, 7) => self.white_kingside = false,
+                (0, 4) => {
+                    self.white_kingside = false;
+                    self.white_queenside = false;
+                }
+                (7, 0) => self.black_queenside = false,
+                (7, 7) => self.black_kingside = false,
+                (7, 4) => {
+                    self.black_kingside = false;
+                    self.black_queenside = false;
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Info {
     position: Position,
     piece: BoardSpot,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+enum MoveParseError {
+    EMPTY,
+    MISSINGSEPARATOR(String),
+    BADFILE(String),
+    BADRANK(String),
+    BADPROMOTION(String),
+    NOPIECE(Position),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveParseError::EMPTY => write!(f, "Digite um lance, ex. e2,e4, e2e4 ou Nf3"),
+            MoveParseError::MISSINGSEPARATOR(input) => write!(
+                f,
+                "'{input}' deve ter duas casas separadas por virgula, ex. e2,e4"
+            ),
+            MoveParseError::BADFILE(square) => write!(
+                f,
+                "'{square}' tem uma coluna invalida, use uma letra de a a h"
+            ),
+            MoveParseError::BADRANK(square) => write!(
+                f,
+                "'{square}' tem uma linha invalida, use um numero de 1 a 8"
+            ),
+            MoveParseError::BADPROMOTION(piece) => {
+                write!(f, "'{piece}' nao e uma peca de promocao, use q, r, b ou n")
+            }
+            MoveParseError::NOPIECE(position) => write!(
+                f,
+                "Escolha uma peca valida, nao ha peca em {}",
+                position.to_notation()
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GameResult {
+    CHECKMATE { winner: Color },
+    STALEMATE,
+    TIMEOUT { winner: Color },
+    DRAW(DrawReason),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DrawReason {
+    THREEFOLD,
+    FIFTYMOVES,
+    FIVEFOLD,
+    SEVENTYFIVEMOVES,
+    INSUFFICIENTMATERIAL,
+    TIMEOUTVSINSUFFICIENTMATERIAL,
+}
+
+impl DrawReason {
+    fn description(&self) -> &'static str {
+        match self {
+            DrawReason::THREEFOLD => "por repeticao tripla",
+            DrawReason::FIFTYMOVES => "pela regra dos 50 lances",
+            DrawReason::FIVEFOLD => "por repeticao quintupla",
+            DrawReason::SEVENTYFIVEMOVES => "pela regra dos 75 lances",
+            DrawReason::INSUFFICIENTMATERIAL => "por material insuficiente",
+            DrawReason::TIMEOUTVSINSUFFICIENTMATERIAL => {
+                "por tempo esgotado sem material para mate"
+            }
+        }
+    }
+}
+
+impl GameResult {
+    fn announcement(&self) -> String {
+        match self {
+            GameResult::CHECKMATE { winner } => {
+                format!("XEQUE-MATE!!! Vitoria das {}", winner.name())
+            }
+            GameResult::STALEMATE => String::from("AFOGAMENTO!!! Empate"),
+            GameResult::TIMEOUT { winner } => {
+                format!("TEMPO ESGOTADO!!! Vitoria das {}", winner.name())
+            }
+            GameResult::DRAW(reason) => {
+                format!("EMPATE!!! Partida empatada {}", reason.description())
+            }
+        }
+    }
+
+    fn score(&self) -> &'static str {
+        match self {
+            GameResult::CHECKMATE {
+                winner: Color::WHITE,
+            }
+            | GameResult::TIMEOUT {
+                winner: Color::WHITE,
+            } => "1-0",
+            GameResult::CHECKMATE {
+                winner: Color::BLACK,
+            }
+            | GameResult::TIMEOUT {
+                winner: Color::BLACK,
+            } => "0-1",
+            GameResult::STALEMATE | GameResult::DRAW(_) => "1/2-1/2",
+        }
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum CheckPiece {
     SAMECOLOR,
@@ -158,7 +359,7 @@ fn diagonals_movement_check(
 ) -> Vec<Position> {
     let mut possible_positions: Vec<Position> = vec![];
     for movement in DIAGONALS {
-        let mut new_position = piece_position.clone();
+        let mut new_position = *piece_position;
         while new_position.line < 8 && new_position.column < 8 {
             new_position.line = (new_position.line as isize + movement[0]) as usize;
             new_position.column = (new_position.column as isize + movement[1]) as usize;
@@ -172,7 +373,7 @@ fn diagonals_movement_check(
             if check_piece == CheckPiece::SAMECOLOR {
                 break;
             } else {
-                possible_positions.push(new_position.clone());
+                possible_positions.push(new_position);
                 if check_piece == CheckPiece::DIFFERENTCOLOR {
                     break;
                 }
@@ -185,7 +386,7 @@ fn diagonals_movement_check(
 fn sides_movement_check(board: &Board, piece: &Piece, piece_position: &Position) -> Vec<Position> {
     let mut possible_positions: Vec<Position> = vec![];
     for movement in SIDES {
-        let mut new_position = piece_position.clone();
+        let mut new_position = *piece_position;
         while new_position.line < 8 && new_position.column < 8 {
             new_position.line = (new_position.line as isize + movement[0]) as usize;
             new_position.column = (new_position.column as isize + movement[1]) as usize;
@@ -199,7 +400,7 @@ fn sides_movement_check(board: &Board, piece: &Piece, piece_position: &Position)
             if check_piece == CheckPiece::SAMECOLOR {
                 break;
             } else {
-                possible_positions.push(new_position.clone());
+                possible_positions.push(new_position);
                 if check_piece == CheckPiece::DIFFERENTCOLOR {
                     break;
                 }
@@ -209,6 +410,16 @@ fn sides_movement_check(board: &Board, piece: &Piece, piece_position: &Position)
     possible_positions
 }
 
+fn letter_to_promotion_piece(letter: char) -> Option<PieceType> {
+    match letter.to_ascii_lowercase() {
+        'q' => Some(PieceType::QUEEN),
+        'r' => Some(PieceType::ROOK),
+        'b' => Some(PieceType::BISHOP),
+        'n' => Some(PieceType::KNIGHT),
+        _ => None,
+    }
+}
+
 fn letter_to_column(letter: char) -> Option<Columns> {
     match letter {
         'a' => Some(Columns::A),
@@ -223,20 +434,36 @@ fn letter_to_column(letter: char) -> Option<Columns> {
     }
 }
 
-fn translate_notation(board: &Board, notation_position: &str) -> Result<Info, &'static str> {
-    let vectorized_position: Vec<char> = notation_position.chars().collect();
+fn notation_to_position(notation: &str) -> Option<Position> {
+    let mut letters = notation.chars();
+    let column = letter_to_column(letters.next()?)?;
+    let line = letters.next()?.to_digit(10)?;
+    if letters.next().is_some() || !(1..=8).contains(&line) {
+        return None;
+    }
+    Some(Position {
+        column: column as usize,
+        line: (line - 1) as usize,
+    })
+}
 
-    let column = letter_to_column(vectorized_position[0]).ok_or("Invalid line")?;
+fn translate_notation(board: &Board, notation_position: &str) -> Result<Info, MoveParseError> {
+    let vectorized_position: Vec<char> = notation_position.chars().collect();
 
-    let line = vectorized_position[1].to_digit(10).ok_or("Invalid line")?;
+    let column = vectorized_position
+        .first()
+        .and_then(|letter| letter_to_column(*letter))
+        .ok_or_else(|| MoveParseError::BADFILE(notation_position.to_string()))?;
 
-    if line > 8 {
-        return Err("Invalid line");
+    let line = match vectorized_position[1..] {
+        [digit] => digit.to_digit(10).filter(|line| (1..=8).contains(line)),
+        _ => None,
     }
+    .ok_or_else(|| MoveParseError::BADRANK(notation_position.to_string()))?;
 
     let piece_position = Position {
-        column: (column as u32) as usize,
-        line: (line as u32 - 1) as usize,
+        column: column as usize,
+        line: (line - 1) as usize,
     };
 
     Ok(Info {
@@ -271,6 +498,7 @@ fn generate_all_pieces_possible_movements(board: &Board, pieces_infos: Vec<Info>
                 board,
                 &piece,
                 &info.position,
+                &None,
             )),
             _ => continue,
         }
@@ -283,6 +511,7 @@ fn generate_possible_movements(
     board: &Board,
     piece: &Piece,
     piece_position: &Position,
+    en_passant: &Option<Position>,
 ) -> Vec<Position> {
     let mut possible_positions: Vec<Position> = vec![];
 
@@ -294,16 +523,15 @@ fn generate_possible_movements(
             ]);
             for (key, value) in pawn_movements.iter() {
                 for movement in value {
-                    if movement[0] == 2 {
-                        if (piece_position.line != 1 && piece.color == Color::WHITE)
-                            || (piece_position.line != 6 && piece.color == Color::BLACK)
-                        {
-                            continue;
-                        }
+                    if movement[0] == 2
+                        && ((piece_position.line != 1 && piece.color == Color::WHITE)
+                            || (piece_position.line != 6 && piece.color == Color::BLACK))
+                    {
+                        continue;
                     }
                     let line = piece_position.line as isize
                         + (if piece.color == Color::BLACK {
-                            movement[0] * -1
+                            -movement[0]
                         } else {
                             movement[0]
                         });
@@ -316,17 +544,27 @@ fn generate_possible_movements(
                         continue;
                     }
                     if key == &"forward" {
+                        // The double step also needs the square it jumps over to be empty
+                        let passing_position = Position {
+                            line: (piece_position.line + new_position.line) / 2,
+                            column: new_position.column,
+                        };
                         if check_piece_in_possible_movement_spot(board, &piece.color, &new_position)
                             == CheckPiece::NOPIECE
+                            && (movement[0] == 1
+                                || board[passing_position.line][passing_position.column]
+                                    == BoardSpot::BLANK)
                         {
                             possible_positions.push(new_position);
                         }
-                    } else {
-                        if check_piece_in_possible_movement_spot(board, &piece.color, &new_position)
-                            == CheckPiece::DIFFERENTCOLOR
-                        {
-                            possible_positions.push(new_position);
-                        }
+                    } else if check_piece_in_possible_movement_spot(
+                        board,
+                        &piece.color,
+                        &new_position,
+                    ) == CheckPiece::DIFFERENTCOLOR
+                        || *en_passant == Some(new_position)
+                    {
+                        possible_positions.push(new_position);
                     }
                 }
             }
@@ -371,10 +609,10 @@ fn generate_possible_movements(
         }
 
         PieceType::QUEEN => {
-            let diagonals_moves = diagonals_movement_check(board, piece, &piece_position);
+            let diagonals_moves = diagonals_movement_check(board, piece, piece_position);
             possible_positions.extend(diagonals_moves);
 
-            let sides_moves = sides_movement_check(board, piece, &piece_position);
+            let sides_moves = sides_movement_check(board, piece, piece_position);
 
             possible_positions.extend(sides_moves);
         }
@@ -405,39 +643,169 @@ fn generate_possible_movements(
     possible_positions
 }
 
-fn check_move(
+fn home_line(color: Color) -> usize {
+    match color {
+        Color::WHITE => 0,
+        Color::BLACK => 7,
+    }
+}
+
+fn is_promotion(piece: &Piece, destination_position: &Position) -> bool {
+    piece.piece_type == PieceType::PAWN
+        && destination_position.line == home_line(piece.color.reverse())
+}
+
+fn apply_movement(
     board: &Board,
     piece: Piece,
     starting_position: &Position,
     destination_position: &Position,
-) -> bool {
-    let possible_movements = generate_possible_movements(board, &piece, &starting_position);
-    println!("POSSIBLE MOVEMENTS");
-    println!("{:?}", possible_movements);
-    if possible_movements.contains(&destination_position) {
-        return true;
+    promotion: Option<PieceType>,
+) -> Board {
+    let mut new_board = *board;
+
+    // A pawn moving diagonally onto an empty square is capturing en passant,
+    // so the captured pawn sits beside the starting square
+    if piece.piece_type == PieceType::PAWN
+        && starting_position.column != destination_position.column
+        && board[destination_position.line][destination_position.column] == BoardSpot::BLANK
+    {
+        new_board[starting_position.line][destination_position.column] = BoardSpot::BLANK;
     }
-    false
+
+    new_board[destination_position.line][destination_position.column] =
+        if is_promotion(&piece, destination_position) {
+            BoardSpot::Piece(Piece {
+                piece_type: promotion.unwrap_or(PieceType::QUEEN),
+                color: piece.color,
+            })
+        } else {
+            BoardSpot::Piece(piece)
+        };
+    new_board[starting_position.line][starting_position.column] = BoardSpot::BLANK;
+
+    // A king moving two columns is castling, so the rook jumps to the other side of it
+    if piece.piece_type == PieceType::KING
+        && starting_position
+            .column
+            .abs_diff(destination_position.column)
+            == 2
+    {
+        let (rook_column, new_rook_column) = if destination_position.column == 6 {
+            (7, 5)
+        } else {
+            (0, 3)
+        };
+        let line = starting_position.line;
+        new_board[line][new_rook_column] = new_board[line][rook_column];
+        new_board[line][rook_column] = BoardSpot::BLANK;
+    }
+    new_board
 }
 
-fn move_piece(
+fn generate_castling_movements(
     board: &Board,
-    piece: Piece,
+    color: Color,
+    castling_rights: &CastlingRights,
+) -> Vec<Position> {
+    let mut possible_positions: Vec<Position> = vec![];
+    let line = home_line(color);
+    let king = Piece {
+        piece_type: PieceType::KING,
+        color,
+    };
+    let rook = BoardSpot::Piece(Piece {
+        piece_type: PieceType::ROOK,
+        color,
+    });
+    let king_position = Position { line, column: 4 };
+
+    if board[line][4] != BoardSpot::Piece(king) || verify_if_was_check(board, color) {
+        return possible_positions;
+    }
+
+    // (has right, rook column, squares that must be empty, square the king passes through, destination)
+    let sides = [
+        (castling_rights.kingside(color), 7, vec![5, 6], 5, 6),
+        (castling_rights.queenside(color), 0, vec![1, 2, 3], 3, 2),
+    ];
+    for (has_right, rook_column, empty_columns, passing_column, destination_column) in sides {
+        if !has_right || board[line][rook_column] != rook {
+            continue;
+        }
+        if empty_columns
+            .iter()
+            .any(|column| board[line][*column] != BoardSpot::BLANK)
+        {
+            continue;
+        }
+        let passing_position = Position {
+            line,
+            column: passing_column,
+        };
+        let passing_board = apply_movement(board, king, &king_position, &passing_position, None);
+        if verify_if_was_check(&passing_board, color) {
+            continue;
+        }
+        // The destination square itself is checked by the legal movement filter
+        possible_positions.push(Position {
+            line,
+            column: destination_column,
+        });
+    }
+    possible_positions
+}
+
+// The square a pawn skipped over with its double step, which the enemy may capture en passant next turn
+fn en_passant_target(
+    piece: &Piece,
     starting_position: &Position,
     destination_position: &Position,
-) -> (Board, bool) {
-    let mut new_board = *board;
-    let is_legal_move = check_move(board, piece, starting_position, &destination_position);
-    if is_legal_move {
-        new_board[destination_position.line][destination_position.column] = BoardSpot::Piece(piece);
-        new_board[starting_position.line][starting_position.column] = BoardSpot::BLANK;
-        (new_board, true)
+) -> Option<Position> {
+    if piece.piece_type == PieceType::PAWN
+        && starting_position.line.abs_diff(destination_position.line) == 2
+    {
+        Some(Position {
+            line: (starting_position.line + destination_position.line) / 2,
+            column: starting_position.column,
+        })
     } else {
-        println!("MOVIMENTO ILEGAL");
-        (new_board, false)
+        None
     }
 }
 
+// The squares a movement can change: both ends, plus the pawn taken en passant or the castling rook
+fn changed_squares(
+    piece: &Piece,
+    starting_position: &Position,
+    destination_position: &Position,
+) -> Vec<Position> {
+    let mut squares = vec![*starting_position, *destination_position];
+    let line = starting_position.line;
+    if piece.piece_type == PieceType::PAWN
+        && starting_position.column != destination_position.column
+    {
+        squares.push(Position {
+            line,
+            column: destination_position.column,
+        });
+    }
+    if piece.piece_type == PieceType::KING
+        && starting_position
+            .column
+            .abs_diff(destination_position.column)
+            == 2
+    {
+        let rook_columns = if destination_position.column == 6 {
+            [7, 5]
+        } else {
+            [0, 3]
+        };
+        squares.extend(rook_columns.map(|column| Position { line, column }));
+    }
+    squares
+}
+
 fn find_king(board: &Board, color: &Color) -> Result<Position, &'static str> {
     for (line_index, line) in board.iter().enumerate() {
         for (column_index, column) in line.iter().enumerate() {
@@ -454,7 +822,7 @@ fn find_king(board: &Board, color: &Color) -> Result<Position, &'static str> {
             }
         }
     }
-    return Err("Error while findig for king");
+    Err("Error while findig for king")
 }
 fn verify_if_was_check(board: &Board, color: Color) -> bool {
     let king_position = find_king(board, &color).unwrap();
@@ -465,40 +833,486 @@ fn verify_if_was_check(board: &Board, color: Color) -> bool {
     all_enemy_possible_movements.contains(&king_position)
 }
 
-fn read_player_move() -> String {
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Movement {
+    starting_position: Position,
+    destination_position: Position,
+    promotion: Option<PieceType>,
+}
+
+// The original array generator is kept so perft can cross-check the bitboard one against it
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum MoveGenerator {
+    ARRAY,
+    BITBOARD,
+}
+
+// Everything make_move overwrites, so unmake_move can put the state back exactly as it was
+#[derive(Clone, Copy, Debug)]
+struct MoveRecord {
+    movement: Movement,
+    board: Board,
+    bitboards: Bitboards,
+    hash: u64,
+    castling_rights: CastlingRights,
+    en_passant: Option<Position>,
+    halfmove_clock: u32,
+}
+
+#[derive(Clone, Debug)]
+struct GameState {
+    board: Board,
+    bitboards: Bitboards,
+    hash: u64,
+    turn: Color,
+    castling_rights: CastlingRights,
+    en_passant: Option<Position>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+    history: Vec<MoveRecord>,
+}
+
+impl GameState {
+    fn new() -> Self {
+        let board = initial_board();
+        let mut state = GameState {
+            board,
+            bitboards: Bitboards::from_board(&board),
+            hash: 0,
+            turn: Color::WHITE,
+            castling_rights: CastlingRights::new(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: vec![],
+        };
+        state.hash = zobrist::hash(&state);
+        state
+    }
+
+    // Applies a movement without validating it, callers are expected to pick it from the legal ones
+    fn make_move(&mut self, movement: Movement) {
+        let Movement {
+            starting_position,
+            destination_position,
+            promotion,
+        } = movement;
+        let piece = match self.board[starting_position.line][starting_position.column] {
+            BoardSpot::Piece(piece) => piece,
+            BoardSpot::BLANK => panic!("No piece to move at {:?}", starting_position),
+        };
+        // Keep the history explicit about promotions, so "e7e8" is recorded as "e7e8q"
+        let promotion = if is_promotion(&piece, &destination_position) {
+            Some(promotion.unwrap_or(PieceType::QUEEN))
+        } else {
+            None
+        };
+        let movement = Movement {
+            starting_position,
+            destination_position,
+            promotion,
+        };
+
+        let mut captured = self.board[destination_position.line][destination_position.column];
+        if piece.piece_type == PieceType::PAWN && Some(destination_position) == self.en_passant {
+            captured = self.board[starting_position.line][destination_position.column];
+        }
+
+        self.history.push(MoveRecord {
+            movement,
+            board: self.board,
+            bitboards: self.bitboards,
+            hash: self.hash,
+            castling_rights: self.castling_rights,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+        });
+
+        let previous_board = self.board;
+        let mut hash = self.hash ^ zobrist::state_key(self);
+        self.board = apply_movement(
+            &self.board,
+            piece,
+            &starting_position,
+            &destination_position,
+            promotion,
+        );
+        for position in changed_squares(&piece, &starting_position, &destination_position) {
+            let square = bitboard::square_index(&position);
+            let spot = self.board[position.line][position.column];
+            self.bitboards.set(square, spot);
+            hash ^= zobrist::piece_key(previous_board[position.line][position.column], square)
+                ^ zobrist::piece_key(spot, square);
+        }
+        self.castling_rights
+            .update(&starting_position, &destination_position);
+        self.en_passant = en_passant_target(&piece, &starting_position, &destination_position);
+        if piece.piece_type == PieceType::PAWN || captured != BoardSpot::BLANK {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Color::BLACK {
+            self.fullmove_number += 1;
+        }
+        self.turn = self.turn.reverse();
+        self.hash = hash ^ zobrist::state_key(self);
+    }
+
+    fn unmake_move(&mut self) -> Option<Movement> {
+        let record = self.history.pop()?;
+        self.board = record.board;
+        self.bitboards = record.bitboards;
+        self.hash = record.hash;
+        self.castling_rights = record.castling_rights;
+        self.en_passant = record.en_passant;
+        self.halfmove_clock = record.halfmove_clock;
+        self.turn = self.turn.reverse();
+        if self.turn == Color::BLACK {
+            self.fullmove_number -= 1;
+        }
+        Some(record.movement)
+    }
+
+    fn is_check(&self) -> bool {
+        self.bitboards.is_in_check(self.turn)
+    }
+
+    // Filters the pseudo-legal movements, dropping the ones that leave the mover's own king in check
+    fn array_legal_movements(&mut self, piece_position: &Position) -> Vec<Position> {
+        let piece = match self.board[piece_position.line][piece_position.column] {
+            BoardSpot::Piece(piece) => piece,
+            BoardSpot::BLANK => return vec![],
+        };
+        let mut possible_movements =
+            generate_possible_movements(&self.board, &piece, piece_position, &self.en_passant);
+        if piece.piece_type == PieceType::KING {
+            possible_movements.extend(generate_castling_movements(
+                &self.board,
+                piece.color,
+                &self.castling_rights,
+            ));
+        }
+        possible_movements
+            .into_iter()
+            .filter(|destination_position| {
+                self.make_move(Movement {
+                    starting_position: *piece_position,
+                    destination_position: *destination_position,
+                    promotion: None,
+                });
+                let was_check = verify_if_was_check(&self.board, piece.color);
+                self.unmake_move();
+                !was_check
+            })
+            .collect()
+    }
+
+    // Every legal movement of the side to move, with one entry per promotion piece
+    fn legal_moves(&mut self) -> Vec<Movement> {
+        self.legal_moves_with(MoveGenerator::BITBOARD)
+    }
+
+    fn legal_moves_with(&mut self, generator: MoveGenerator) -> Vec<Movement> {
+        if generator == MoveGenerator::BITBOARD {
+            return bitboard::legal_moves(self);
+        }
+        let mut moves: Vec<Movement> = vec![];
+        for info in find_all_one_color_pieces(&self.board, self.turn) {
+            let piece = match info.piece {
+                BoardSpot::Piece(piece) => piece,
+                BoardSpot::BLANK => continue,
+            };
+            for destination_position in self.array_legal_movements(&info.position) {
+                if is_promotion(&piece, &destination_position) {
+                    for promotion in [
+                        PieceType::QUEEN,
+                        PieceType::ROOK,
+                        PieceType::BISHOP,
+                        PieceType::KNIGHT,
+                    ] {
+                        moves.push(Movement {
+                            starting_position: info.position,
+                            destination_position,
+                            promotion: Some(promotion),
+                        });
+                    }
+                } else {
+                    moves.push(Movement {
+                        starting_position: info.position,
+                        destination_position,
+                        promotion: None,
+                    });
+                }
+            }
+        }
+        moves
+    }
+
+    // The destinations the piece on the square can legally reach
+    fn legal_movements(&mut self, piece_position: &Position) -> Vec<Position> {
+        let mut destinations: Vec<Position> = vec![];
+        for movement in self.legal_moves() {
+            if movement.starting_position == *piece_position
+                && !destinations.contains(&movement.destination_position)
+            {
+                destinations.push(movement.destination_position);
+            }
+        }
+        destinations
+    }
+
+    fn check_move(&mut self, movement: &Movement) -> bool {
+        let start = movement.starting_position;
+        match self.board[start.line][start.column] {
+            BoardSpot::Piece(piece) if piece.color == self.turn => {}
+            _ => return false,
+        }
+        self.legal_movements(&movement.starting_position)
+            .contains(&movement.destination_position)
+    }
+
+    fn move_piece(&mut self, movement: Movement) -> bool {
+        if self.check_move(&movement) {
+            self.make_move(movement);
+            true
+        } else {
+            println!("MOVIMENTO ILEGAL");
+            false
+        }
+    }
+
+    // The game is over when the side to move has no legal movement left
+    fn game_result(&mut self) -> Option<GameResult> {
+        if self.legal_moves().is_empty() {
+            return if self.is_check() {
+                Some(GameResult::CHECKMATE {
+                    winner: self.turn.reverse(),
+                })
+            } else {
+                Some(GameResult::STALEMATE)
+            };
+        }
+        // Unlike the threefold repetition and the fifty-move rule, these need nobody to claim them
+        if self.repetition_count() >= 5 {
+            Some(GameResult::DRAW(DrawReason::FIVEFOLD))
+        } else if self.halfmove_clock >= 150 {
+            Some(GameResult::DRAW(DrawReason::SEVENTYFIVEMOVES))
+        } else {
+            None
+        }
+    }
+
+    // How many times the current position has occurred, counting this one. Only the movements
+    // since the last capture or pawn move can lead back to it
+    fn repetition_count(&self) -> usize {
+        1 + self
+            .history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .filter(|record| record.hash == self.hash)
+            .count()
+    }
+
+    // A draw the side to move may claim, but does not have to
+    fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetition_count() >= 3 {
+            Some(DrawReason::THREEFOLD)
+        } else if self.halfmove_clock >= 100 {
+            Some(DrawReason::FIFTYMOVES)
+        } else {
+            None
+        }
+    }
+}
+
+// Returns None once the input is closed
+fn read_player_move() -> Option<String> {
     print!("Your move: ");
     stdout().flush().unwrap();
 
     let mut player_move = String::new();
-    stdin()
-        .read_line(&mut player_move)
-        .expect("Error computing your move!");
-    player_move
+    match stdin().read_line(&mut player_move) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(player_move),
+    }
 }
 
-fn show_board(board: &Board) {
-    let mut line_number = 8;
-    for line in board.iter().rev() {
-        print!("{} ", line_number);
-        for spot in line.iter() {
-            print!("{} ", spot.to_board_representation());
+fn read_promotion_piece() -> PieceType {
+    loop {
+        print!("Promover para (q, r, b, n): ");
+        stdout().flush().unwrap();
+
+        let mut answer = String::new();
+        let read_bytes = stdin()
+            .read_line(&mut answer)
+            .expect("Error computing your promotion!");
+        if read_bytes == 0 {
+            return PieceType::QUEEN;
+        }
+
+        let mut letters = answer.trim().chars();
+        if let (Some(letter), None) = (letters.next(), letters.next()) {
+            if let Some(piece_type) = letter_to_promotion_piece(letter) {
+                return piece_type;
+            }
         }
-        line_number -= 1;
-        println!("");
+        println!("Escolha uma peca valida");
     }
-    print!("  ");
-    for letter in 'A'..'I' {
-        print!("{} ", letter);
+}
+
+fn show_board(state: &GameState, view: &BoardView) {
+    print!("{}", view.render(state, &[]));
+}
+
+// Draws the board with the legal destinations of the piece on the square marked, and lists them
+// Returns whether the board was drawn
+fn show_hint(state: &mut GameState, view: &BoardView, square: &str) -> bool {
+    let position = match translate_notation(&state.board, square) {
+        Ok(Info {
+            piece: BoardSpot::Piece(piece),
+            position,
+        }) if piece.color == state.turn => position,
+        Ok(Info {
+            piece: BoardSpot::Piece(_),
+            ..
+        }) => {
+            println!("Nao e o seu turno");
+            return false;
+        }
+        Ok(Info { position, .. }) => {
+            println!("{}", MoveParseError::NOPIECE(position));
+            return false;
+        }
+        Err(err) => {
+            println!("{err}");
+            return false;
+        }
+    };
+
+    let movements: Vec<Movement> = state
+        .legal_moves()
+        .into_iter()
+        .filter(|movement| movement.starting_position == position)
+        .collect();
+    if movements.is_empty() {
+        println!("Nenhum lance possivel para {square}");
+        return false;
     }
-    println!("");
-    println!("");
+    let destinations: Vec<Position> = movements
+        .iter()
+        .map(|movement| movement.destination_position)
+        .collect();
+    print!("{}", view.render(state, &destinations));
+    let sans: Vec<String> = movements
+        .iter()
+        .map(|movement| state.movement_to_san(movement))
+        .collect();
+    println!("Lances possiveis para {square}: {}", sans.join(", "));
+    true
 }
 
-fn get_info(board: &Board, player_move: String) -> [Result<Info, &'static str>; 2] {
-    let positions: Vec<&str> = player_move.split(',').map(|s| s.trim()).collect();
-    let start = translate_notation(board, positions[0]);
-    let end = translate_notation(board, positions[1]);
-    [start, end]
+// Without a square every piece that can move is marked and all the legal moves are listed
+fn show_all_moves(state: &mut GameState, view: &BoardView) -> bool {
+    let movements = state.legal_moves();
+    let movable: Vec<Position> = movements
+        .iter()
+        .map(|movement| movement.starting_position)
+        .collect();
+    print!("{}", view.render(state, &movable));
+    let sans: Vec<String> = movements
+        .iter()
+        .map(|movement| state.movement_to_san(movement))
+        .collect();
+    println!("Lances possiveis: {}", sans.join(", "));
+    true
+}
+
+// Splits an optional promotion suffix, given either as "=N" or as a trailing letter ("e7e8q")
+fn split_promotion(player_move: &str) -> Result<(&str, Option<PieceType>), MoveParseError> {
+    if let Some((movement, piece_letter)) = player_move.split_once('=') {
+        let piece_letter = piece_letter.trim();
+        let mut letters = piece_letter.chars();
+        return match (letters.next(), letters.next()) {
+            (Some(letter), None) => letter_to_promotion_piece(letter)
+                .map(|piece_type| (movement, Some(piece_type)))
+                .ok_or_else(|| MoveParseError::BADPROMOTION(piece_letter.to_string())),
+            _ => Err(MoveParseError::BADPROMOTION(piece_letter.to_string())),
+        };
+    }
+
+    let letters: Vec<char> = player_move.chars().collect();
+    if !player_move.contains(',') && letters.len() == 5 {
+        let piece_type = letter_to_promotion_piece(letters[4])
+            .ok_or_else(|| MoveParseError::BADPROMOTION(letters[4].to_string()))?;
+        let movement_length = player_move.len() - letters[4].len_utf8();
+        return Ok((&player_move[..movement_length], Some(piece_type)));
+    }
+
+    Ok((player_move, None))
+}
+
+// Coordinate input is either comma separated ("e2,e4") or two glued squares ("e2e4", "e7e8q"),
+// anything else is read as SAN. Squares off the board like "e9e4" still count, so get_info can
+// say which file or rank is wrong
+fn is_coordinate_notation(player_move: &str) -> bool {
+    let letters: Vec<char> = player_move.chars().collect();
+    let looks_like_square =
+        |file: char, rank: char| file.is_ascii_lowercase() && rank.is_ascii_digit();
+    player_move.contains(',')
+        || (letters.len() >= 4
+            && looks_like_square(letters[0], letters[1])
+            && looks_like_square(letters[2], letters[3]))
+}
+
+// Resolves a SAN move, asking for the promotion piece when the player left it out
+fn read_san_move(state: &mut GameState, player_move: &str) -> Result<Movement, SanError> {
+    match state.parse_san(player_move) {
+        Err(SanError::MISSINGPROMOTION(_)) => {
+            let piece_type = read_promotion_piece();
+            state.parse_san(&format!(
+                "{}={}",
+                player_move.trim_end_matches(['+', '#', '!', '?']),
+                san::piece_type_to_san_letter(piece_type)
+            ))
+        }
+        parsed => parsed,
+    }
+}
+
+fn get_info(
+    board: &Board,
+    player_move: String,
+) -> Result<([Info; 2], Option<PieceType>), MoveParseError> {
+    let player_move = player_move.trim();
+    if player_move.is_empty() {
+        return Err(MoveParseError::EMPTY);
+    }
+    let (movement, promotion) = split_promotion(player_move)?;
+    let positions: Vec<&str> = if movement.contains(',') {
+        movement.split(',').map(|s| s.trim()).collect()
+    } else if movement.chars().count() == 4 {
+        let middle = movement.char_indices().nth(2).unwrap().0;
+        let (start, end) = movement.split_at(middle);
+        vec![start, end]
+    } else {
+        vec![movement]
+    };
+    let [start, end] = positions[..] else {
+        return Err(MoveParseError::MISSINGSEPARATOR(player_move.to_string()));
+    };
+    if start.is_empty() || end.is_empty() {
+        return Err(MoveParseError::MISSINGSEPARATOR(player_move.to_string()));
+    }
+
+    let start = translate_notation(board, start);
+    let end = translate_notation(board, end);
+    let [start, end] = [start, end].unwrap_array()?;
+    if start.piece == BoardSpot::BLANK {
+        return Err(MoveParseError::NOPIECE(start.position));
+    }
+    Ok(([start, end], promotion))
 }
 
 fn find_all_one_color_pieces(board: &Board, color: Color) -> Vec<Info> {
@@ -529,8 +1343,72 @@ fn find_all_one_color_pieces(board: &Board, color: Color) -> Vec<Info> {
     pieces
 }
 
-fn main() {
-    let mut board: Board = [
+fn non_king_pieces(board: &Board, color: Color) -> Vec<(PieceType, Position)> {
+    find_all_one_color_pieces(board, color)
+        .into_iter()
+        .filter_map(|info| match info.piece {
+            BoardSpot::Piece(piece) if piece.piece_type != PieceType::KING => {
+                Some((piece.piece_type, info.position))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_minor(piece_type: PieceType) -> bool {
+    piece_type == PieceType::BISHOP || piece_type == PieceType::KNIGHT
+}
+
+// Neither side can ever mate: K v K, K+B v K, K+N v K, or K+B v K+B with both bishops on the same square color
+fn is_insufficient_material(board: &Board) -> bool {
+    let [white, black] = [Color::WHITE, Color::BLACK].map(|color| non_king_pieces(board, color));
+    match (white.as_slice(), black.as_slice()) {
+        ([], []) => true,
+        ([(piece_type, _)], []) | ([], [(piece_type, _)]) => is_minor(*piece_type),
+        ([(PieceType::BISHOP, white_bishop)], [(PieceType::BISHOP, black_bishop)]) => {
+            (white_bishop.line + white_bishop.column) % 2
+                == (black_bishop.line + black_bishop.column) % 2
+        }
+        _ => false,
+    }
+}
+
+// FIDE article 6.9: running out of time is only a draw when the opponent could not mate by any
+// series of legal moves. The flagged side's pieces count too, as they can hem their own king in,
+// so K+N v K+P is still a win
+fn has_mating_material(board: &Board, color: Color) -> bool {
+    let opponent = non_king_pieces(board, color.reverse());
+    let square_color = |position: &Position| (position.line + position.column) % 2;
+    match non_king_pieces(board, color).as_slice() {
+        [] => false,
+        [(PieceType::KNIGHT, _)] => !opponent.is_empty(),
+        // Bishops that all run on one square color never attack the other one, only a blocker
+        // standing there lets them mate
+        bishops @ [(_, first), ..]
+            if bishops.iter().all(|(piece_type, position)| {
+                *piece_type == PieceType::BISHOP && square_color(position) == square_color(first)
+            }) =>
+        {
+            opponent.iter().any(|(piece_type, position)| {
+                *piece_type != PieceType::BISHOP || square_color(position) != square_color(first)
+            })
+        }
+        _ => true,
+    }
+}
+
+fn timeout_result(board: &Board, flagged: Color) -> GameResult {
+    if has_mating_material(board, flagged.reverse()) {
+        GameResult::TIMEOUT {
+            winner: flagged.reverse(),
+        }
+    } else {
+        GameResult::DRAW(DrawReason::TIMEOUTVSINSUFFICIENTMATERIAL)
+    }
+}
+
+fn initial_board() -> Board {
+    [
         [
             BoardSpot::Piece(Piece {
                 piece_type: PieceType::ROOK,
@@ -707,72 +1585,446 @@ fn main() {
                 color: Color::BLACK,
             }),
         ],
-    ];
+    ]
+}
+
+const DEFAULT_ENGINE_DEPTH: u32 = 4;
+
+struct Arguments {
+    fen: Option<String>,
+    pgn: Option<String>,
+    uci: bool,
+    engine: Option<Color>,
+    depth: Option<u32>,
+    movetime: Option<Duration>,
+    time_control: Option<Vec<clock::Stage>>,
+    ascii: bool,
+    orientation: Option<Orientation>,
+    tui: bool,
+}
+
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Arguments, String> {
+    let mut parsed = Arguments {
+        fen: None,
+        pgn: None,
+        uci: false,
+        engine: None,
+        depth: None,
+        movetime: None,
+        time_control: None,
+        ascii: false,
+        orientation: None,
+        tui: false,
+    };
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--fen" => {
+                let fen = arguments
+                    .next()
+                    .ok_or("--fen expects a FEN string, e.g. --fen \"<fen>\"")?;
+                parsed.fen = Some(fen);
+            }
+            "--uci" => parsed.uci = true,
+            "--pgn" => {
+                let path = arguments
+                    .next()
+                    .ok_or("--pgn expects the path of a PGN file")?;
+                parsed.pgn = Some(path);
+            }
+            "--engine" => {
+                parsed.engine = match arguments.next().as_deref() {
+                    Some("white") => Some(Color::WHITE),
+                    Some("black") => Some(Color::BLACK),
+                    _ => return Err(String::from("--engine expects 'white' or 'black'")),
+                };
+            }
+            "--depth" => {
+                let depth = arguments
+                    .next()
+                    .and_then(|depth| depth.parse().ok())
+                    .filter(|depth| *depth > 0)
+                    .ok_or("--depth expects a positive number of plies")?;
+                parsed.depth = Some(depth);
+            }
+            "--movetime" => {
+                let milliseconds = arguments
+                    .next()
+                    .and_then(|milliseconds| milliseconds.parse().ok())
+                    .ok_or("--movetime expects a number of milliseconds")?;
+                parsed.movetime = Some(Duration::from_millis(milliseconds));
+            }
+            "--ascii" => parsed.ascii = true,
+            "--tui" => parsed.tui = true,
+            "--orientation" => {
+                parsed.orientation = match arguments.next().as_deref() {
+                    Some("white") => Some(Orientation::WHITE),
+                    Some("black") => Some(Orientation::BLACK),
+                    Some("auto") => Some(Orientation::AUTO),
+                    _ => {
+                        return Err(String::from(
+                            "--orientation expects 'white', 'black' or 'auto'",
+                        ))
+                    }
+                };
+            }
+            "--tc" => {
+                let text = arguments
+                    .next()
+                    .ok_or("--tc expects a time control, e.g. --tc 5+3 or --tc 40/90+30:30+30")?;
+                let stages = clock::parse_time_control(&text).map_err(|err| err.to_string())?;
+                parsed.time_control = Some(stages);
+            }
+            _ => return Err(format!("Unknown argument '{argument}'")),
+        }
+    }
+    Ok(parsed)
+}
+
+fn engine_limits(arguments: &Arguments, clock: Option<&Clock>, turn: Color) -> SearchLimits {
+    match clock {
+        // Without an explicit limit the engine plays on its clock
+        Some(clock) if arguments.depth.is_none() && arguments.movetime.is_none() => SearchLimits {
+            white_time: Some(clock.remaining(Color::WHITE, turn)),
+            black_time: Some(clock.remaining(Color::BLACK, turn)),
+            white_increment: clock.bonus_time(Color::WHITE),
+            black_increment: clock.bonus_time(Color::BLACK),
+            moves_to_go: clock.moves_to_go(turn),
+            ..SearchLimits::default()
+        },
+        _ => SearchLimits {
+            depth: arguments.depth.or(match arguments.movetime {
+                Some(_) => None,
+                None => Some(DEFAULT_ENGINE_DEPTH),
+            }),
+            movetime: arguments.movetime,
+            ..SearchLimits::default()
+        },
+    }
+}
+
+fn finish_game(state: &GameState, result: GameResult, pgn_path: &str) {
+    println!("{}", result.announcement());
+    println!("Resultado final: {}", result.score());
+    match pgn::save_pgn(pgn_path, state, Some(result)) {
+        Ok(()) => println!("Partida salva em {pgn_path}"),
+        Err(err) => println!("Nao foi possivel salvar a partida: {err}"),
+    }
+}
 
-    println!("");
+fn main() {
+    let arguments = match parse_arguments(env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(2);
+        }
+    };
+
+    if arguments.uci {
+        uci::run_uci();
+        return;
+    }
+
+    // https://no-color.org asks for plain output whenever NO_COLOR is set and not empty
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let view = BoardView {
+        style: if arguments.ascii || no_color {
+            Style::ASCII
+        } else {
+            Style::ANSI
+        },
+        // Against the engine the board faces the human by default
+        orientation: arguments.orientation.unwrap_or(match arguments.engine {
+            Some(Color::WHITE) => Orientation::BLACK,
+            _ => Orientation::WHITE,
+        }),
+    };
+
+    if let Some(path) = &arguments.pgn {
+        if let Err(err) = viewer::run_viewer(path, &view) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut state = match &arguments.fen {
+        Some(fen) => match GameState::from_fen(fen) {
+            Ok(state) => state,
+            Err(err) => {
+                eprintln!("Invalid FEN: {err}");
+                process::exit(2);
+            }
+        },
+        None => GameState::new(),
+    };
+
+    let pgn_path = pgn::default_file_name();
+
+    println!();
     println!("{:-^40}", "TERMINAL CHESS");
-    println!("");
+    println!();
 
-    let mut turn = Color::WHITE;
-    loop {
-        show_board(&board);
-        let was_check = verify_if_was_check(&board, turn);
-        if was_check {
+    let mut clock = arguments.time_control.clone().map(Clock::new);
+
+    if arguments.tui {
+        match tui::run_tui(&mut state, &arguments, &view, &mut clock) {
+            Ok(Some(result)) => {
+                finish_game(&state, result, &pgn_path);
+                return;
+            }
+            Ok(None) => return,
+            Err(err) => eprintln!(
+                "Could not start the terminal UI ({err}), falling back to the line interface"
+            ),
+        }
+    }
+
+    // Movements taken back with undo, replayed by redo until a new movement is made
+    let mut redo_stack: Vec<Movement> = vec![];
+    let mut known_plies = state.history.len();
+    let mut skip_board = false;
+    // The rest of a line of several UCI moves, most recent last, played one per iteration so each
+    // gets the same checks as a typed move
+    let mut queued_moves: Vec<String> = vec![];
+    let mut queued_plies: Option<usize> = None;
+
+    let result = loop {
+        // A queued move that could not be played drops the rest of the line
+        if queued_plies.take() == Some(state.history.len()) {
+            queued_moves.clear();
+        }
+        // A movement was made since the last iteration, by the player or the engine
+        if state.history.len() != known_plies {
+            redo_stack.clear();
+            // One press per ply, the last one made by the side that is not to move
+            let new_plies = state.history.len() - known_plies;
+            let mut mover = if new_plies % 2 == 1 {
+                state.turn.reverse()
+            } else {
+                state.turn
+            };
+            known_plies = state.history.len();
+            let mut flagged = None;
+            for _ in 0..new_plies {
+                if clock.as_mut().is_some_and(|clock| !clock.press(mover)) {
+                    flagged = Some(mover);
+                    break;
+                }
+                mover = mover.reverse();
+            }
+            if let Some(mover) = flagged {
+                break timeout_result(&state.board, mover);
+            }
+        }
+        // A hint already drew the board with its marks, drawing it again would push them away
+        if !std::mem::take(&mut skip_board) {
+            show_board(&state, &view);
+            if let Some(clock) = &clock {
+                clock.show(state.turn);
+            }
+            if let Some(record) = state.history.last() {
+                println!("Ultimo lance: {}", uci::movement_to_uci(&record.movement));
+            }
+        }
+        if let Some(result) = state.game_result() {
+            break result;
+        }
+        if is_insufficient_material(&state.board) {
+            break GameResult::DRAW(DrawReason::INSUFFICIENTMATERIAL);
+        }
+
+        if state.is_check() {
             println!("XEQUE!!!");
         }
+        let claimable_draw = state.claimable_draw();
+        if let Some(reason) = claimable_draw {
+            println!(
+                "Empate disponivel {}, digite 'draw' para aceitar",
+                reason.description()
+            );
+        }
 
-        let player_move = read_player_move();
+        if arguments.engine == Some(state.turn) {
+            if !queued_moves.is_empty() {
+                queued_moves.reverse();
+                println!(
+                    "Vez do computador, lances ignorados: {}",
+                    queued_moves.join(" ")
+                );
+                queued_moves.clear();
+            }
+            let limits = engine_limits(&arguments, clock.as_ref(), state.turn);
+            let stop = AtomicBool::new(false);
+            if let Some(movement) = engine::search(&mut state, &limits, &stop, &mut |_| {}) {
+                println!("O computador jogou: {}", state.movement_to_san(&movement));
+                state.make_move(movement);
+            }
+            continue;
+        }
 
-        let [start, end] = match get_info(&board, player_move).unwrap_array() {
-            Ok(positions) => positions,
-            Err(err) => {
-                println!("{err}");
-                continue;
+        let player_move = match queued_moves.pop() {
+            Some(queued) => {
+                queued_plies = Some(state.history.len());
+                queued
             }
+            None => match read_player_move() {
+                Some(player_move) => player_move,
+                None => {
+                    println!();
+                    return;
+                }
+            },
         };
+        if clock
+            .as_ref()
+            .is_some_and(|clock| clock.is_flagged(state.turn))
+        {
+            break timeout_result(&state.board, state.turn);
+        }
 
-        match start.piece {
-            BoardSpot::Piece(piece) => {
-                if piece.color != turn {
-                    println!("Nao e o seu turno");
+        let command: Vec<&str> = player_move.split_whitespace().collect();
+        match command.as_slice() {
+            ["draw"] => match claimable_draw {
+                Some(reason) => break GameResult::DRAW(reason),
+                None => {
+                    println!("Nenhum empate disponivel");
                     continue;
                 }
-                let (new_board, was_moved) =
-                    move_piece(&board, piece, &start.position, &end.position);
-                board = new_board;
-                if was_moved {
-                    turn = turn.reverse();
+            },
+            // Against the engine both take back and replay its reply too, so the player is to move
+            ["undo"] => {
+                match state.unmake_move() {
+                    Some(movement) => {
+                        redo_stack.push(movement);
+                        if arguments.engine == Some(state.turn) {
+                            redo_stack.extend(state.unmake_move());
+                        }
+                    }
+                    None => println!("Nenhum lance para desfazer"),
+                }
+                if let Some(clock) = &mut clock {
+                    for _ in state.history.len()..known_plies {
+                        clock.take_back();
+                    }
+                }
+                known_plies = state.history.len();
+                continue;
+            }
+            ["redo"] => {
+                match redo_stack.pop() {
+                    Some(movement) => {
+                        state.make_move(movement);
+                        if arguments.engine == Some(state.turn) {
+                            if let Some(reply) = redo_stack.pop() {
+                                state.make_move(reply);
+                            }
+                        }
+                    }
+                    None => println!("Nenhum lance para refazer"),
+                }
+                if let Some(clock) = &mut clock {
+                    for _ in known_plies..state.history.len() {
+                        clock.replay();
+                    }
                 }
+                known_plies = state.history.len();
+                continue;
+            }
+            ["moves" | "hint"] => {
+                skip_board = show_all_moves(&mut state, &view);
+                continue;
+            }
+            ["moves" | "hint", square] => {
+                skip_board = show_hint(&mut state, &view, square);
+                continue;
+            }
+            ["fen"] => {
+                println!("{}", state.to_fen());
+                continue;
+            }
+            ["save"] | ["save", _] => {
+                let path = command.get(1).copied().unwrap_or(&pgn_path);
+                match pgn::save_pgn(path, &state, None) {
+                    Ok(()) => println!("Partida salva em {path}"),
+                    Err(err) => println!("Nao foi possivel salvar a partida: {err}"),
+                }
+                continue;
+            }
+            ["perft" | "divide", depth] | ["perft" | "divide", depth, "array"] => {
+                let Ok(depth) = depth.parse::<u32>() else {
+                    println!("Invalid depth '{depth}'");
+                    continue;
+                };
+                let generator = if command.len() == 3 {
+                    MoveGenerator::ARRAY
+                } else {
+                    MoveGenerator::BITBOARD
+                };
+                let started = Instant::now();
+                let nodes = if command[0] == "divide" {
+                    let results = perft::divide(&mut state, depth, generator);
+                    for (movement, nodes) in &results {
+                        println!("{}: {nodes}", uci::movement_to_uci(movement));
+                    }
+                    results.iter().map(|(_, nodes)| nodes).sum()
+                } else {
+                    perft::perft(&mut state, depth, generator)
+                };
+                println!("Nodes: {nodes} ({:.3}s)", started.elapsed().as_secs_f64());
+                continue;
+            }
+            _ => {}
+        }
+
+        // A line of several UCI moves, e.g. piped from another program, is played in order
+        if command.len() > 1
+            && command
+                .iter()
+                .all(|token| uci::uci_to_movement(token).is_some())
+        {
+            queued_moves = command
+                .iter()
+                .rev()
+                .map(|token| token.to_string())
+                .collect();
+            skip_board = true;
+            continue;
+        }
+
+        if !player_move.trim().is_empty() && !is_coordinate_notation(player_move.trim()) {
+            match read_san_move(&mut state, player_move.trim()) {
+                Ok(movement) => state.make_move(movement),
+                Err(err) => println!("{err}"),
             }
-            BoardSpot::BLANK => {
-                println!("Escolha uma peca valida");
+            continue;
+        }
+
+        let ([start, end], mut promotion) = match get_info(&state.board, player_move) {
+            Ok(positions) => positions,
+            Err(err) => {
+                println!("{err}");
                 continue;
             }
+        };
+
+        // get_info already rejected empty starting squares
+        let BoardSpot::Piece(piece) = start.piece else {
+            continue;
+        };
+        if piece.color != state.turn {
+            println!("Nao e o seu turno");
+            continue;
+        }
+        if is_promotion(&piece, &end.position) && promotion.is_none() {
+            promotion = Some(read_promotion_piece());
         }
+        state.move_piece(Movement {
+            starting_position: start.position,
+            destination_position: end.position,
+            promotion,
+        });
+    };
 
-        // if (piece_color == "White" && is_white_turn) || (piece_color == "Black" && !is_white_turn) {
-        //     let (new_board, was_moved) = move_piece(&board, &start, &end);
-        //     board = new_board;
-        //     if was_moved {
-        //         is_white_turn = !is_white_turn;
-        //
-        //         let new_position = Info {
-        //             position: Position {
-        //                 line: end.position.line,
-        //                 column: end.position.column,
-        //             },
-        //             piece: start.piece,
-        //         };
-        //         /* let was_check = verify_if_was_check(&board, &new_position); */
-        //
-        //         //TO DO
-        //         if was_check {
-        //             loop {}
-        //         }
-        //     }
-        //     /* show_board(&board); */
-        // } else {
-        //     println!("NAO E SUA VEZ DE JOGAR");
-        // }
-    }
+    finish_game(&state, result, &pgn_path);
 }
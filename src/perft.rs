@@ -0,0 +1,113 @@
+use crate::{GameState, MoveGenerator, Movement};
+
+// Counts the leaf nodes of the legal move tree, the standard check for move generators
+pub fn perft(state: &mut GameState, depth: u32, generator: MoveGenerator) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = state.legal_moves_with(generator);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for movement in moves {
+        state.make_move(movement);
+        nodes += perft(state, depth - 1, generator);
+        state.unmake_move();
+    }
+    nodes
+}
+
+// Perft split by root move, handy to find which move a generator bug hides under
+pub fn divide(state: &mut GameState, depth: u32, generator: MoveGenerator) -> Vec<(Movement, u64)> {
+    let mut results = vec![];
+    for movement in state.legal_moves_with(generator) {
+        state.make_move(movement);
+        let nodes = perft(state, depth.saturating_sub(1), generator);
+        state.unmake_move();
+        results.push((movement, nodes));
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference counts from https://www.chessprogramming.org/Perft_Results,
+    // which both generators have to reproduce
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let mut state = GameState::from_fen(fen).unwrap();
+        for generator in [MoveGenerator::ARRAY, MoveGenerator::BITBOARD] {
+            for (depth, nodes) in expected.iter().enumerate() {
+                assert_eq!(
+                    perft(&mut state, depth as u32 + 1, generator),
+                    *nodes,
+                    "perft({}) of {fen} with the {generator:?} generator",
+                    depth + 1
+                );
+            }
+        }
+        assert_eq!(
+            state.to_fen(),
+            fen,
+            "perft must leave the position untouched"
+        );
+    }
+
+    #[test]
+    fn start_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902],
+        );
+    }
+
+    #[test]
+    fn kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    fn position_3() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+    }
+
+    #[test]
+    fn position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn position_4_mirrored() {
+        assert_perft(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn position_6() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        );
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::fen::FenError;
+use crate::san::SanError;
+use crate::{Color, GameResult, GameState};
+
+const STANDARD_START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const MAX_LINE_LENGTH: usize = 80;
+
+// Converts days since the unix epoch into a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn seconds_since_epoch() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+fn today() -> String {
+    let (year, month, day) = civil_from_days(seconds_since_epoch().div_euclid(86_400));
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+// A file name that is unique per game, e.g. terminal-chess-20260118-153012.pgn
+pub fn default_file_name() -> String {
+    let seconds = seconds_since_epoch();
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let time_of_day = seconds.rem_euclid(86_400);
+    format!(
+        "terminal-chess-{year:04}{month:02}{day:02}-{:02}{:02}{:02}.pgn",
+        time_of_day / 3_600,
+        time_of_day % 3_600 / 60,
+        time_of_day % 60
+    )
+}
+
+pub fn result_token(result: Option<GameResult>) -> &'static str {
+    result.map_or("*", |result| result.score())
+}
+
+pub fn write_pgn(state: &GameState, result: Option<GameResult>) -> String {
+    let mut replay = state.clone();
+    while replay.unmake_move().is_some() {}
+    let starting_fen = replay.to_fen();
+    let movements: Vec<_> = state.history.iter().map(|record| record.movement).collect();
+
+    let mut pgn = String::new();
+    for (tag, value) in [
+        ("Event", "Terminal Chess game".to_string()),
+        ("Site", "?".to_string()),
+        ("Date", today()),
+        ("Round", "-".to_string()),
+        ("White", "?".to_string()),
+        ("Black", "?".to_string()),
+        ("Result", result_token(result).to_string()),
+    ] {
+        pgn.push_str(&format!("[{tag} \"{value}\"]\n"));
+    }
+    if starting_fen != STANDARD_START_FEN {
+        pgn.push_str("[SetUp \"1\"]\n");
+        pgn.push_str(&format!("[FEN \"{starting_fen}\"]\n"));
+    }
+    pgn.push('\n');
+
+    let mut tokens: Vec<String> = vec![];
+    for (index, movement) in movements.iter().enumerate() {
+        if replay.turn == Color::WHITE {
+            tokens.push(format!("{}.", replay.fullmove_number));
+        } else if index == 0 {
+            tokens.push(format!("{}...", replay.fullmove_number));
+        }
+        tokens.push(replay.movement_to_san(movement));
+        replay.make_move(*movement);
+    }
+    tokens.push(result_token(result).to_string());
+
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+pub fn save_pgn(path: &str, state: &GameState, result: Option<GameResult>) -> io::Result<()> {
+    fs::write(path, write_pgn(state, result))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    UNTERMINATEDTAG,
+    UNTERMINATEDCOMMENT,
+    UNBALANCEDVARIATION,
+    UNEXPECTEDTOKEN(String),
+    NOGAME,
+    INVALIDFEN(FenError),
+    ILLEGALMOVE {
+        ply: usize,
+        san: String,
+        reason: SanError,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::UNTERMINATEDTAG => write!(f, "A tag pair is missing its closing ']'"),
+            PgnError::UNTERMINATEDCOMMENT => write!(f, "A comment is missing its closing '}}'"),
+            PgnError::UNBALANCEDVARIATION => write!(f, "Variation parentheses are not balanced"),
+            PgnError::UNEXPECTEDTOKEN(token) => write!(f, "Unexpected '{token}' in the movetext"),
+            PgnError::NOGAME => write!(f, "No game was found"),
+            PgnError::INVALIDFEN(err) => write!(f, "Invalid FEN tag: {err}"),
+            PgnError::ILLEGALMOVE { ply, san, reason } => {
+                write!(f, "Illegal move '{san}' at ply {ply}: {reason}")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u32>,
+    pub comments: Vec<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    TAG(String, String),
+    COMMENT(String),
+    NAG(u32),
+    OPENVARIATION,
+    CLOSEVARIATION,
+    RESULT(String),
+    SAN(String),
+}
+
+// Move suffix annotations and the NAGs they stand for
+const SUFFIX_ANNOTATIONS: [(&str, u32); 6] = [
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+    ("!", 1),
+    ("?", 2),
+];
+
+fn read_until(characters: &mut Peekable<Chars>, end: char) -> Option<String> {
+    let mut text = String::new();
+    for character in characters.by_ref() {
+        if character == end {
+            return Some(text);
+        }
+        text.push(character);
+    }
+    None
+}
+
+fn parse_tag(text: &str) -> Result<Token, PgnError> {
+    let (name, value) = text
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or(PgnError::UNTERMINATEDTAG)?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .ok_or(PgnError::UNTERMINATEDTAG)?;
+    Ok(Token::TAG(
+        name.to_string(),
+        value.replace("\\\"", "\"").replace("\\\\", "\\"),
+    ))
+}
+
+fn push_word(word: &str, tokens: &mut Vec<Token>) {
+    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&word) {
+        tokens.push(Token::RESULT(word.to_string()));
+        return;
+    }
+    // Move numbers may be glued to the move, as in "12.Nf3" or "12...Nf6"
+    let word = match word.rfind('.') {
+        Some(index) if word.starts_with(|c: char| c.is_ascii_digit()) => &word[index + 1..],
+        _ => word,
+    };
+    if word.is_empty() {
+        return;
+    }
+    for (suffix, nag) in SUFFIX_ANNOTATIONS {
+        if let Some(san) = word.strip_suffix(suffix) {
+            tokens.push(Token::SAN(san.to_string()));
+            tokens.push(Token::NAG(nag));
+            return;
+        }
+    }
+    tokens.push(Token::SAN(word.to_string()));
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens: Vec<Token> = vec![];
+    let mut characters = text.chars().peekable();
+    let mut line_start = true;
+    while let Some(character) = characters.next() {
+        match character {
+            '%' if line_start => {
+                read_until(&mut characters, '\n');
+            }
+            '[' => {
+                let tag = read_until(&mut characters, ']').ok_or(PgnError::UNTERMINATEDTAG)?;
+                tokens.push(parse_tag(&tag)?);
+            }
+            '{' => {
+                let comment =
+                    read_until(&mut characters, '}').ok_or(PgnError::UNTERMINATEDCOMMENT)?;
+                tokens.push(Token::COMMENT(comment.trim().to_string()));
+            }
+            ';' => {
+                let comment = read_until(&mut characters, '\n').unwrap_or_default();
+                tokens.push(Token::COMMENT(comment.trim().to_string()));
+                line_start = true;
+                continue;
+            }
+            '(' => tokens.push(Token::OPENVARIATION),
+            ')' => tokens.push(Token::CLOSEVARIATION),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(digit) = characters.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                let nag = digits
+                    .parse()
+                    .map_err(|_| PgnError::UNEXPECTEDTOKEN(format!("${digits}")))?;
+                tokens.push(Token::NAG(nag));
+            }
+            character if character.is_whitespace() => {}
+            character => {
+                let mut word = String::from(character);
+                while let Some(next) =
+                    characters.next_if(|next| !next.is_whitespace() && !"[]{}();$".contains(*next))
+                {
+                    word.push(next);
+                }
+                push_word(&word, &mut tokens);
+            }
+        }
+        line_start = character == '\n';
+    }
+    Ok(tokens)
+}
+
+fn parse_movetext(
+    tokens: &mut Peekable<std::vec::IntoIter<Token>>,
+    depth: usize,
+) -> Result<(Vec<PgnMove>, Option<String>), PgnError> {
+    let mut moves: Vec<PgnMove> = vec![];
+    while let Some(token) = tokens.peek() {
+        // A tag after the movetext starts the next game of the file
+        if matches!(token, Token::TAG(_, _)) {
+            break;
+        }
+        let token = tokens.next().unwrap();
+        match token {
+            Token::SAN(san) => moves.push(PgnMove {
+                san,
+                ..PgnMove::default()
+            }),
+            Token::NAG(nag) => {
+                if let Some(last_move) = moves.last_mut() {
+                    last_move.nags.push(nag);
+                }
+            }
+            Token::COMMENT(comment) => {
+                if let Some(last_move) = moves.last_mut() {
+                    last_move.comments.push(comment);
+                }
+            }
+            Token::OPENVARIATION => {
+                let (variation, _) = parse_movetext(tokens, depth + 1)?;
+                moves
+                    .last_mut()
+                    .ok_or(PgnError::UNBALANCEDVARIATION)?
+                    .variations
+                    .push(variation);
+            }
+            Token::CLOSEVARIATION => {
+                if depth == 0 {
+                    return Err(PgnError::UNBALANCEDVARIATION);
+                }
+                return Ok((moves, None));
+            }
+            Token::RESULT(result) => {
+                if depth > 0 {
+                    return Err(PgnError::UNEXPECTEDTOKEN(result));
+                }
+                return Ok((moves, Some(result)));
+            }
+            Token::TAG(_, _) => unreachable!(),
+        }
+    }
+    if depth > 0 {
+        return Err(PgnError::UNBALANCEDVARIATION);
+    }
+    Ok((moves, None))
+}
+
+// Reads the first game of a PGN text
+pub fn parse_pgn(text: &str) -> Result<PgnGame, PgnError> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    let mut game = PgnGame::default();
+    while let Some(Token::TAG(name, value)) =
+        tokens.next_if(|token| matches!(token, Token::TAG(_, _)))
+    {
+        game.tags.push((name, value));
+    }
+    let (moves, result) = parse_movetext(&mut tokens, 0)?;
+    if game.tags.is_empty() && moves.is_empty() {
+        return Err(PgnError::NOGAME);
+    }
+    game.moves = moves;
+    game.result = result;
+    Ok(game)
+}
+
+// Plays the main line through the legal movement validation, returning the final state with its history
+pub fn replay(game: &PgnGame) -> Result<GameState, PgnError> {
+    let mut state = match game.tag("FEN") {
+        Some(fen) => GameState::from_fen(fen).map_err(PgnError::INVALIDFEN)?,
+        None => GameState::new(),
+    };
+    for (index, pgn_move) in game.moves.iter().enumerate() {
+        let movement = state
+            .parse_san(&pgn_move.san)
+            .map_err(|reason| PgnError::ILLEGALMOVE {
+                ply: index + 1,
+                san: pgn_move.san.clone(),
+                reason,
+            })?;
+        state.make_move(movement);
+    }
+    Ok(state)
+}
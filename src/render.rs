@@ -0,0 +1,182 @@
+use crate::fen::piece_to_letter;
+use crate::{find_king, BoardSpot, Color, GameState, Movement, Piece, Position};
+
+const RESET: &str = "\x1b[0m";
+
+// Colors from the 256 color palette, shared with the terminal UI
+pub const LIGHT_SQUARE: u8 = 180;
+pub const DARK_SQUARE: u8 = 137;
+pub const LAST_MOVE_SQUARE: u8 = 143;
+pub const CHECK_SQUARE: u8 = 167;
+pub const MARKED_SQUARE: u8 = 108;
+pub const WHITE_PIECE: u8 = 231;
+pub const BLACK_PIECE: u8 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    // Colored squares with highlights, every piece drawn with the filled glyph in its own color
+    ANSI,
+    // Plain letters, PNBRQK for White and pnbrqk for Black, for terminals without colors
+    ASCII,
+}
+
+pub struct Highlights<'a> {
+    last_move: Option<Movement>,
+    check: Option<Position>,
+    marked: &'a [Position],
+}
+
+impl<'a> Highlights<'a> {
+    pub fn new(state: &GameState, marked: &'a [Position]) -> Self {
+        Highlights {
+            last_move: state.history.last().map(|record| record.movement),
+            check: if state.is_check() {
+                find_king(&state.board, &state.turn).ok()
+            } else {
+                None
+            },
+            marked,
+        }
+    }
+
+    // Marked squares win over a king in check, which wins over the last move
+    pub fn background(&self, position: Position) -> u8 {
+        if self.marked.contains(&position) {
+            MARKED_SQUARE
+        } else if self.check == Some(position) {
+            CHECK_SQUARE
+        } else if self.last_move.is_some_and(|movement| {
+            movement.starting_position == position || movement.destination_position == position
+        }) {
+            LAST_MOVE_SQUARE
+        } else if (position.line + position.column).is_multiple_of(2) {
+            DARK_SQUARE
+        } else {
+            LIGHT_SQUARE
+        }
+    }
+}
+
+// The filled glyphs read best on both backgrounds, the color tells the sides apart
+pub fn piece_glyph(piece: Piece) -> char {
+    BoardSpot::Piece(Piece {
+        piece_type: piece.piece_type,
+        color: Color::WHITE,
+    })
+    .to_board_representation()
+}
+
+pub fn piece_foreground(color: Color) -> u8 {
+    match color {
+        Color::WHITE => WHITE_PIECE,
+        Color::BLACK => BLACK_PIECE,
+    }
+}
+
+pub fn ascii_glyph(spot: BoardSpot) -> char {
+    match spot {
+        BoardSpot::Piece(piece) => piece_to_letter(&piece),
+        BoardSpot::BLANK => '.',
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    WHITE,
+    BLACK,
+    // Turns the board around every move, so the side to move always sits at the bottom
+    AUTO,
+}
+
+pub struct BoardView {
+    pub style: Style,
+    pub orientation: Orientation,
+}
+
+impl BoardView {
+    pub fn bottom(&self, turn: Color) -> Color {
+        match self.orientation {
+            Orientation::WHITE => Color::WHITE,
+            Orientation::BLACK => Color::BLACK,
+            Orientation::AUTO => turn,
+        }
+    }
+
+    // The square drawn at a screen row and column, counted from the top left corner of the board
+    pub fn square_at(&self, turn: Color, row: usize, screen_column: usize) -> Position {
+        match self.bottom(turn) {
+            Color::WHITE => Position {
+                line: 7 - row,
+                column: screen_column,
+            },
+            Color::BLACK => Position {
+                line: row,
+                column: 7 - screen_column,
+            },
+        }
+    }
+
+    // Marked squares carry a '*', after the piece letter when there is something to capture
+    fn ascii_square(&self, spot: BoardSpot, marked: bool) -> String {
+        match (spot, marked) {
+            (BoardSpot::Piece(_), true) => format!("{}*", ascii_glyph(spot)),
+            (BoardSpot::BLANK, true) => String::from("* "),
+            (_, false) => format!("{} ", ascii_glyph(spot)),
+        }
+    }
+
+    fn ansi_square(
+        &self,
+        state: &GameState,
+        position: Position,
+        highlights: &Highlights,
+    ) -> String {
+        let background = highlights.background(position);
+        match state.board[position.line][position.column] {
+            BoardSpot::Piece(piece) => {
+                let weight = match piece.color {
+                    Color::WHITE => "1;",
+                    Color::BLACK => "",
+                };
+                format!(
+                    "\x1b[48;5;{background}m\x1b[{weight}38;5;{}m {} {RESET}",
+                    piece_foreground(piece.color),
+                    piece_glyph(piece)
+                )
+            }
+            BoardSpot::BLANK => format!("\x1b[48;5;{background}m   {RESET}"),
+        }
+    }
+
+    // Draws the board with the marked squares, e.g. the destinations of a piece, standing out
+    pub fn render(&self, state: &GameState, marked: &[Position]) -> String {
+        let highlights = Highlights::new(state, marked);
+        let mut output = String::new();
+        for row in 0..8 {
+            let line = self.square_at(state.turn, row, 0).line;
+            output.push_str(&format!("{} ", line + 1));
+            for screen_column in 0..8 {
+                let position = self.square_at(state.turn, row, screen_column);
+                let Position { line, column } = position;
+                output.push_str(&match self.style {
+                    Style::ANSI => self.ansi_square(state, position, &highlights),
+                    Style::ASCII => {
+                        self.ascii_square(state.board[line][column], marked.contains(&position))
+                    }
+                });
+            }
+            output.push('\n');
+        }
+        output.push_str("  ");
+        for screen_column in 0..8 {
+            let column = self.square_at(state.turn, 0, screen_column).column;
+            let letter = (b'A' + column as u8) as char;
+            output.push_str(&match self.style {
+                Style::ANSI => format!(" {letter} "),
+                Style::ASCII => format!("{letter} "),
+            });
+        }
+        output.push_str("\n\n");
+        output
+    }
+}
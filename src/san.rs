@@ -0,0 +1,292 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::{
+    find_all_one_color_pieces, letter_to_promotion_piece, notation_to_position, BoardSpot,
+    GameResult, GameState, Movement, Piece, PieceType, Position,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum SanError {
+    MALFORMED(String),
+    ILLEGAL(String),
+    AMBIGUOUS {
+        san: String,
+        candidates: Vec<Position>,
+    },
+    MISSINGPROMOTION(String),
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SanError::MALFORMED(san) => write!(f, "'{san}' is not a valid SAN move"),
+            SanError::ILLEGAL(san) => write!(f, "'{san}' is not a legal move in this position"),
+            SanError::AMBIGUOUS { san, candidates } => {
+                let squares: Vec<String> = candidates
+                    .iter()
+                    .map(|position| position.to_notation())
+                    .collect();
+                write!(
+                    f,
+                    "'{san}' is ambiguous, it could be played from {}; add the file or rank of the piece (e.g. Nbd2)",
+                    squares.join(" or ")
+                )
+            }
+            SanError::MISSINGPROMOTION(san) => write!(
+                f,
+                "'{san}' reaches the last rank, add the promotion piece (e.g. {san}=Q)"
+            ),
+        }
+    }
+}
+
+fn san_regex() -> &'static Regex {
+    static SAN_REGEX: OnceLock<Regex> = OnceLock::new();
+    SAN_REGEX.get_or_init(|| {
+        Regex::new(r"^([NBRQK])?([a-h])?([1-8])?x?([a-h][1-8])(?:=?([NBRQnbrq]))?$").unwrap()
+    })
+}
+
+fn san_letter_to_piece_type(letter: &str) -> PieceType {
+    match letter {
+        "R" => PieceType::ROOK,
+        "N" => PieceType::KNIGHT,
+        "B" => PieceType::BISHOP,
+        "Q" => PieceType::QUEEN,
+        "K" => PieceType::KING,
+        _ => PieceType::PAWN,
+    }
+}
+
+pub fn piece_type_to_san_letter(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::PAWN => "",
+        PieceType::ROOK => "R",
+        PieceType::KNIGHT => "N",
+        PieceType::BISHOP => "B",
+        PieceType::QUEEN => "Q",
+        PieceType::KING => "K",
+    }
+}
+
+impl GameState {
+    // Writes a legal movement in standard algebraic notation, it must be called before the movement is made
+    pub fn movement_to_san(&mut self, movement: &Movement) -> String {
+        let Movement {
+            starting_position,
+            destination_position,
+            promotion,
+        } = *movement;
+        let piece = match self.board[starting_position.line][starting_position.column] {
+            BoardSpot::Piece(piece) => piece,
+            BoardSpot::BLANK => panic!("No piece to move at {:?}", starting_position),
+        };
+
+        let mut san = String::new();
+        if piece.piece_type == PieceType::KING
+            && starting_position
+                .column
+                .abs_diff(destination_position.column)
+                == 2
+        {
+            san.push_str(if destination_position.column == 6 {
+                "O-O"
+            } else {
+                "O-O-O"
+            });
+        } else {
+            let is_capture = self.board[destination_position.line][destination_position.column]
+                != BoardSpot::BLANK
+                || (piece.piece_type == PieceType::PAWN
+                    && starting_position.column != destination_position.column);
+            let notation = starting_position.to_notation();
+
+            if piece.piece_type == PieceType::PAWN {
+                if is_capture {
+                    san.push_str(&notation[..1]);
+                }
+            } else {
+                san.push_str(piece_type_to_san_letter(piece.piece_type));
+                san.push_str(&self.disambiguation(&piece, movement));
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&destination_position.to_notation());
+            if let Some(piece_type) = promotion {
+                san.push('=');
+                san.push_str(piece_type_to_san_letter(piece_type));
+            }
+        }
+
+        self.make_move(*movement);
+        if self.is_check() {
+            san.push(match self.game_result() {
+                Some(GameResult::CHECKMATE { .. }) => '#',
+                _ => '+',
+            });
+        }
+        self.unmake_move();
+        san
+    }
+
+    // Finds the legal movement a SAN string describes, ignoring check marks and annotations like "!?"
+    pub fn parse_san(&mut self, san: &str) -> Result<Movement, SanError> {
+        let san = san.trim();
+        let stripped = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal_moves = self.legal_moves();
+
+        let is_castling = |movement: &&Movement, destination_column: usize| {
+            self.board[movement.starting_position.line][movement.starting_position.column]
+                == BoardSpot::Piece(Piece {
+                    piece_type: PieceType::KING,
+                    color: self.turn,
+                })
+                && movement.starting_position.column == 4
+                && movement.destination_position.column == destination_column
+        };
+        let castling_column = match stripped {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(destination_column) = castling_column {
+            return legal_moves
+                .iter()
+                .find(|movement| is_castling(movement, destination_column))
+                .copied()
+                .ok_or_else(|| SanError::ILLEGAL(san.to_string()));
+        }
+
+        let captures = san_regex()
+            .captures(stripped)
+            .ok_or_else(|| SanError::MALFORMED(san.to_string()))?;
+        let piece_type = captures.get(1).map_or(PieceType::PAWN, |letter| {
+            san_letter_to_piece_type(letter.as_str())
+        });
+        let from_column = captures
+            .get(2)
+            .map(|letter| letter.as_str().as_bytes()[0] - b'a');
+        let from_line = captures
+            .get(3)
+            .map(|digit| digit.as_str().as_bytes()[0] - b'1');
+        let destination_position = notation_to_position(&captures[4])
+            .ok_or_else(|| SanError::MALFORMED(san.to_string()))?;
+        let promotion = captures
+            .get(5)
+            .and_then(|letter| letter.as_str().chars().next())
+            .and_then(letter_to_promotion_piece);
+
+        let piece = BoardSpot::Piece(Piece {
+            piece_type,
+            color: self.turn,
+        });
+        let candidates: Vec<&Movement> = legal_moves
+            .iter()
+            .filter(|movement| {
+                let start = movement.starting_position;
+                self.board[start.line][start.column] == piece
+                    && movement.destination_position == destination_position
+                    && from_column.is_none_or(|column| start.column == column as usize)
+                    && from_line.is_none_or(|line| start.line == line as usize)
+                    && (promotion.is_none() || movement.promotion == promotion)
+            })
+            .collect();
+
+        // Without a piece every promotion matches, so the player has to be asked which one
+        if promotion.is_none()
+            && candidates
+                .iter()
+                .any(|movement| movement.promotion.is_some())
+        {
+            return Err(SanError::MISSINGPROMOTION(san.to_string()));
+        }
+        match candidates.as_slice() {
+            [] => Err(SanError::ILLEGAL(san.to_string())),
+            [movement] => Ok(**movement),
+            _ => Err(SanError::AMBIGUOUS {
+                san: san.to_string(),
+                candidates: candidates
+                    .iter()
+                    .map(|movement| movement.starting_position)
+                    .collect(),
+            }),
+        }
+    }
+
+    // The file, rank or whole square needed to tell the piece apart from its twins reaching the same square
+    fn disambiguation(&mut self, piece: &Piece, movement: &Movement) -> String {
+        let starting_position = movement.starting_position;
+        let twins: Vec<_> = find_all_one_color_pieces(&self.board, piece.color)
+            .into_iter()
+            .filter(|info| {
+                info.piece == BoardSpot::Piece(*piece) && info.position != starting_position
+            })
+            .map(|info| info.position)
+            .collect();
+        let rivals: Vec<_> = twins
+            .into_iter()
+            .filter(|position| {
+                self.legal_movements(position)
+                    .contains(&movement.destination_position)
+            })
+            .collect();
+
+        let notation = starting_position.to_notation();
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals
+            .iter()
+            .all(|position| position.column != starting_position.column)
+        {
+            notation[..1].to_string()
+        } else if rivals
+            .iter()
+            .all(|position| position.line != starting_position.line)
+        {
+            notation[1..].to_string()
+        } else {
+            notation
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn promotions_without_a_piece_ask_for_one() {
+        let mut state = GameState::from_fen("3r4/4P1k1/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        for san in ["e8", "exd8+", "exd8"] {
+            assert_eq!(
+                state.parse_san(san),
+                Err(SanError::MISSINGPROMOTION(san.to_string()))
+            );
+        }
+
+        let queen = state.parse_san("e8=Q").unwrap();
+        assert_eq!(queen.destination_position.to_notation(), "e8");
+        assert_eq!(queen.promotion, Some(PieceType::QUEEN));
+        let knight = state.parse_san("exd8=N+").unwrap();
+        assert_eq!(knight.destination_position.to_notation(), "d8");
+        assert_eq!(knight.promotion, Some(PieceType::KNIGHT));
+
+        // A promotion piece on a movement that does not reach the last rank is not a legal move
+        assert_eq!(
+            state.parse_san("Kd2=Q"),
+            Err(SanError::ILLEGAL(String::from("Kd2=Q")))
+        );
+        assert_eq!(
+            state.parse_san("Ke2"),
+            Ok(Movement {
+                starting_position: notation_to_position("e1").unwrap(),
+                destination_position: notation_to_position("e2").unwrap(),
+                promotion: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use std::io::{self, stdout, Stdout, Write};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEventKind,
+};
+use crossterm::style::{
+    Attribute, Color as TermColor, Print, SetAttribute, SetBackgroundColor, SetForegroundColor,
+};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::clock::{format_duration, Clock};
+use crate::render::{self, BoardView, Highlights, Style};
+use crate::{
+    engine, engine_limits, is_insufficient_material, is_promotion, letter_to_promotion_piece,
+    timeout_result, Arguments, BoardSpot, Color, DrawReason, GameResult, GameState, Movement,
+    Piece, PieceType, Position,
+};
+
+const BOARD_TOP: u16 = 1;
+const BOARD_LEFT: u16 = 3;
+const SQUARE_WIDTH: u16 = 3;
+const PANEL_LEFT: u16 = BOARD_LEFT + 8 * SQUARE_WIDTH + 4;
+const CLOCK_ROW: u16 = BOARD_TOP + 1;
+const STATUS_ROW: u16 = BOARD_TOP + 10;
+const MOVE_LIST_ROWS: usize = 12;
+
+// Only the terminal UI has a cursor and a picked piece, the other colors come from render
+const SELECTED_SQUARE: u8 = 74;
+const CURSOR_SQUARE: u8 = 220;
+
+// Puts the terminal back the way it was, even when the UI stops with an error
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let guard = RawTerminal;
+        execute!(stdout(), EnterAlternateScreen, EnableMouseCapture, Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, DisableMouseCapture, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+enum Step {
+    CONTINUE,
+    // No input arrived, only the clocks can have changed on screen
+    IDLE,
+    QUIT,
+    OVER(GameResult),
+}
+
+struct Tui<'a> {
+    state: &'a mut GameState,
+    arguments: &'a Arguments,
+    view: &'a BoardView,
+    clock: &'a mut Option<Clock>,
+    // Screen row and column of the cursor, so the arrows keep their direction when the board flips
+    cursor: (usize, usize),
+    selected: Option<Position>,
+    destinations: Vec<Position>,
+    // A pawn movement to the last rank waiting for the promotion piece
+    promotion: Option<Movement>,
+    sans: Vec<String>,
+    // Movements taken back with undo, most recent last, dropped as soon as a new one is played
+    redo_stack: Vec<Movement>,
+    first_move_number: u32,
+    black_moved_first: bool,
+    message: String,
+}
+
+impl Tui<'_> {
+    fn automatic_result(&mut self) -> Option<GameResult> {
+        self.state.game_result().or_else(|| {
+            is_insufficient_material(&self.state.board)
+                .then_some(GameResult::DRAW(DrawReason::INSUFFICIENTMATERIAL))
+        })
+    }
+
+    fn play(&mut self, movement: Movement) -> Step {
+        let mover = self.state.turn;
+        self.sans.push(self.state.movement_to_san(&movement));
+        self.state.make_move(movement);
+        self.redo_stack.clear();
+        self.selected = None;
+        self.destinations.clear();
+        self.message.clear();
+        if self.clock.as_mut().is_some_and(|clock| !clock.press(mover)) {
+            return Step::OVER(timeout_result(&self.state.board, mover));
+        }
+        match self.automatic_result() {
+            Some(result) => Step::OVER(result),
+            None => Step::CONTINUE,
+        }
+    }
+
+    // Against the engine its reply is taken back too, so the player is to move again
+    fn undo(&mut self) {
+        let Some(movement) = self.state.unmake_move() else {
+            self.message = String::from("Nenhum lance para desfazer");
+            return;
+        };
+        self.sans.pop();
+        self.redo_stack.push(movement);
+        let mut plies = 1;
+        if self.arguments.engine == Some(self.state.turn) {
+            if let Some(movement) = self.state.unmake_move() {
+                self.sans.pop();
+                self.redo_stack.push(movement);
+                plies += 1;
+            }
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            for _ in 0..plies {
+                clock.take_back();
+            }
+        }
+        self.selected = None;
+        self.destinations.clear();
+        self.message.clear();
+    }
+
+    // Against the engine its reply is replayed too, so the player is to move again
+    fn redo(&mut self) {
+        let Some(movement) = self.redo_stack.pop() else {
+            self.message = String::from("Nenhum lance para refazer");
+            return;
+        };
+        self.replay(movement);
+        let mut plies = 1;
+        if self.arguments.engine == Some(self.state.turn) {
+            if let Some(reply) = self.redo_stack.pop() {
+                self.replay(reply);
+                plies += 1;
+            }
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            for _ in 0..plies {
+                clock.replay();
+            }
+        }
+        self.selected = None;
+        self.destinations.clear();
+        self.message.clear();
+    }
+
+    fn replay(&mut self, movement: Movement) {
+        self.sans.push(self.state.movement_to_san(&movement));
+        self.state.make_move(movement);
+    }
+
+    // Picks a piece of the side to move, or moves the picked piece to one of its destinations
+    fn activate(&mut self, position: Position) -> Step {
+        if let Some(start) = self.selected {
+            if self.destinations.contains(&position) {
+                let movement = Movement {
+                    starting_position: start,
+                    destination_position: position,
+                    promotion: None,
+                };
+                let BoardSpot::Piece(piece) = self.state.board[start.line][start.column] else {
+                    return Step::CONTINUE;
+                };
+                if is_promotion(&piece, &position) {
+                    self.promotion = Some(movement);
+                    self.message =
+                        String::from("Promover para: [q] dama [r] torre [b] bispo [n] cavalo");
+                    return Step::CONTINUE;
+                }
+                return self.play(movement);
+            }
+        }
+
+        match self.state.board[position.line][position.column] {
+            BoardSpot::Piece(piece) if piece.color == self.state.turn => {
+                self.selected = Some(position);
+                self.destinations = self.state.legal_movements(&position);
+                self.message = if self.destinations.is_empty() {
+                    format!("Nenhum lance possivel para {}", position.to_notation())
+                } else {
+                    String::new()
+                };
+            }
+            _ => {
+                self.selected = None;
+                self.destinations.clear();
+            }
+        }
+        Step::CONTINUE
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Step {
+        // Raw mode delivers Ctrl-C as an ordinary key instead of a signal
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Step::QUIT;
+        }
+        let code = key.code;
+        if let Some(movement) = self.promotion {
+            match code {
+                KeyCode::Char(letter) => {
+                    if let Some(piece_type) = letter_to_promotion_piece(letter) {
+                        self.promotion = None;
+                        return self.play(Movement {
+                            promotion: Some(piece_type),
+                            ..movement
+                        });
+                    }
+                }
+                KeyCode::Esc => {
+                    self.promotion = None;
+                    self.message.clear();
+                }
+                _ => {}
+            }
+            return Step::CONTINUE;
+        }
+
+        let (row, column) = self.cursor;
+        match code {
+            KeyCode::Up => self.cursor = (row.saturating_sub(1), column),
+            KeyCode::Down => self.cursor = ((row + 1).min(7), column),
+            KeyCode::Left => self.cursor = (row, column.saturating_sub(1)),
+            KeyCode::Right => self.cursor = (row, (column + 1).min(7)),
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                return self.activate(self.view.square_at(self.state.turn, row, column));
+            }
+            KeyCode::Esc => {
+                self.selected = None;
+                self.destinations.clear();
+                self.message.clear();
+            }
+            KeyCode::Char('u') => self.undo(),
+            KeyCode::Char('r') => self.redo(),
+            KeyCode::Char('d') => match self.state.claimable_draw() {
+                Some(reason) => return Step::OVER(GameResult::DRAW(reason)),
+                None => self.message = String::from("Nenhum empate disponivel"),
+            },
+            KeyCode::Char('q') => return Step::QUIT,
+            _ => {}
+        }
+        Step::CONTINUE
+    }
+
+    fn handle_click(&mut self, x: u16, y: u16) -> Step {
+        let board_right = BOARD_LEFT + 8 * SQUARE_WIDTH;
+        if !(BOARD_TOP..BOARD_TOP + 8).contains(&y) || !(BOARD_LEFT..board_right).contains(&x) {
+            return Step::CONTINUE;
+        }
+        let row = (y - BOARD_TOP) as usize;
+        let column = ((x - BOARD_LEFT) / SQUARE_WIDTH) as usize;
+        self.cursor = (row, column);
+        if self.promotion.is_some() {
+            return Step::CONTINUE;
+        }
+        self.activate(self.view.square_at(self.state.turn, row, column))
+    }
+
+    // The pieces the given color has taken so far, read back from the board before each capture
+    fn captured_by(&self, color: Color) -> String {
+        let mut captured = String::new();
+        for record in &self.state.history {
+            let start = record.movement.starting_position;
+            let destination = record.movement.destination_position;
+            let BoardSpot::Piece(mover) = record.board[start.line][start.column] else {
+                continue;
+            };
+            if mover.color != color {
+                continue;
+            }
+            match record.board[destination.line][destination.column] {
+                BoardSpot::Piece(piece) => captured.push(self.glyph(BoardSpot::Piece(piece))),
+                BoardSpot::BLANK
+                    if mover.piece_type == PieceType::PAWN
+                        && start.column != destination.column =>
+                {
+                    captured.push(self.glyph(BoardSpot::Piece(Piece {
+                        piece_type: PieceType::PAWN,
+                        color: color.reverse(),
+                    })));
+                }
+                BoardSpot::BLANK => {}
+            }
+        }
+        captured
+    }
+
+    fn move_list(&self) -> Vec<String> {
+        let mut lines: Vec<String> = vec![];
+        let mut number = self.first_move_number;
+        let mut black_to_move = self.black_moved_first;
+        for san in &self.sans {
+            if !black_to_move {
+                lines.push(format!("{number}. {san}"));
+            } else {
+                match lines.last_mut() {
+                    Some(line) => line.push_str(&format!(" {san}")),
+                    None => lines.push(format!("{number}... {san}")),
+                }
+                number += 1;
+            }
+            black_to_move = !black_to_move;
+        }
+        lines
+    }
+
+    // Outlined and filled glyphs in the side panel, or letters when colors are turned off
+    fn glyph(&self, spot: BoardSpot) -> char {
+        match self.view.style {
+            Style::ANSI => spot.to_board_representation(),
+            Style::ASCII => render::ascii_glyph(spot),
+        }
+    }
+
+    fn draw_square(
+        &self,
+        out: &mut Stdout,
+        row: usize,
+        screen_column: usize,
+        highlights: &Highlights,
+    ) -> io::Result<()> {
+        let position = self.view.square_at(self.state.turn, row, screen_column);
+        let spot = self.state.board[position.line][position.column];
+        let is_cursor = (row, screen_column) == self.cursor;
+        let is_selected = self.selected == Some(position);
+        queue!(
+            out,
+            MoveTo(
+                BOARD_LEFT + screen_column as u16 * SQUARE_WIDTH,
+                BOARD_TOP + row as u16
+            )
+        )?;
+        match self.view.style {
+            // Without colors the cursor is bracketed, the picked piece parenthesized and its
+            // destinations starred like on the line board
+            Style::ASCII => {
+                let (open, close) = if is_cursor {
+                    ('[', ']')
+                } else if is_selected {
+                    ('(', ')')
+                } else if self.destinations.contains(&position) {
+                    (' ', '*')
+                } else {
+                    (' ', ' ')
+                };
+                queue!(
+                    out,
+                    Print(format!("{open}{}{close}", render::ascii_glyph(spot)))
+                )
+            }
+            Style::ANSI => {
+                let background = if is_cursor {
+                    CURSOR_SQUARE
+                } else if is_selected {
+                    SELECTED_SQUARE
+                } else {
+                    highlights.background(position)
+                };
+                let (glyph, foreground, weight) = match spot {
+                    BoardSpot::Piece(piece) => (
+                        render::piece_glyph(piece),
+                        render::piece_foreground(piece.color),
+                        match piece.color {
+                            Color::WHITE => Attribute::Bold,
+                            Color::BLACK => Attribute::NormalIntensity,
+                        },
+                    ),
+                    BoardSpot::BLANK => (' ', render::BLACK_PIECE, Attribute::NormalIntensity),
+                };
+                queue!(
+                    out,
+                    SetBackgroundColor(TermColor::AnsiValue(background)),
+                    SetForegroundColor(TermColor::AnsiValue(foreground)),
+                    SetAttribute(weight),
+                    Print(format!(" {glyph} ")),
+                    SetAttribute(Attribute::Reset)
+                )
+            }
+        }
+    }
+
+    // Redrawing only these lines while waiting for input keeps the screen from flickering
+    fn draw_clocks(&self, out: &mut Stdout) -> io::Result<()> {
+        let Some(clock) = self.clock.as_ref() else {
+            return Ok(());
+        };
+        for (index, color) in [Color::WHITE, Color::BLACK].into_iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(PANEL_LEFT, CLOCK_ROW + index as u16),
+                Print(format!(
+                    "Relogio {}: {}",
+                    color.name(),
+                    format_duration(clock.remaining(color, self.state.turn))
+                )),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw(&self) -> io::Result<()> {
+        let mut out = stdout();
+        let turn = self.state.turn;
+        queue!(out, Clear(ClearType::All))?;
+
+        let highlights = Highlights::new(self.state, &self.destinations);
+        for row in 0..8 {
+            let line = self.view.square_at(turn, row, 0).line;
+            queue!(out, MoveTo(1, BOARD_TOP + row as u16), Print(line + 1))?;
+            for screen_column in 0..8 {
+                self.draw_square(&mut out, row, screen_column, &highlights)?;
+            }
+        }
+        for screen_column in 0..8 {
+            let column = self.view.square_at(turn, 0, screen_column).column;
+            queue!(
+                out,
+                MoveTo(
+                    BOARD_LEFT + screen_column as u16 * SQUARE_WIDTH + 1,
+                    BOARD_TOP + 8
+                ),
+                Print((b'A' + column as u8) as char)
+            )?;
+        }
+
+        queue!(
+            out,
+            MoveTo(PANEL_LEFT, BOARD_TOP),
+            Print(format!("Vez das {}", turn.name()))
+        )?;
+        self.draw_clocks(&mut out)?;
+        let mut panel: Vec<String> = vec![String::new()];
+        for color in [Color::WHITE, Color::BLACK] {
+            panel.push(format!(
+                "Capturas das {}: {}",
+                color.name(),
+                self.captured_by(color)
+            ));
+        }
+        panel.push(String::new());
+        panel.push(String::from("Lances:"));
+        let moves = self.move_list();
+        panel.extend(
+            moves
+                .iter()
+                .skip(moves.len().saturating_sub(MOVE_LIST_ROWS))
+                .cloned(),
+        );
+        let clock_rows = if self.clock.is_some() { 2 } else { 0 };
+        for (index, text) in panel.iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(PANEL_LEFT, CLOCK_ROW + clock_rows + index as u16),
+                Print(text)
+            )?;
+        }
+
+        let status = if !self.message.is_empty() {
+            self.message.clone()
+        } else if let Some(reason) = self.state.claimable_draw() {
+            format!(
+                "Empate disponivel {}, tecle 'd' para aceitar",
+                reason.description()
+            )
+        } else if self.state.is_check() {
+            String::from("XEQUE!!!")
+        } else {
+            String::new()
+        };
+        let help =
+            "Setas/mouse: escolher casa  Enter: selecionar  Esc: cancelar  u: desfazer  r: refazer  q: sair";
+        queue!(
+            out,
+            MoveTo(1, STATUS_ROW),
+            Print(status),
+            MoveTo(1, STATUS_ROW + 1),
+            Print(help)
+        )?;
+        out.flush()
+    }
+
+    fn run(&mut self) -> io::Result<Option<GameResult>> {
+        if let Some(result) = self.automatic_result() {
+            return self.finish(result);
+        }
+        let mut redraw = true;
+        loop {
+            if redraw {
+                self.draw()?;
+            }
+            let step = if self.arguments.engine == Some(self.state.turn) {
+                let limits = engine_limits(self.arguments, self.clock.as_ref(), self.state.turn);
+                let stop = AtomicBool::new(false);
+                match engine::search(self.state, &limits, &stop, &mut |_| {}) {
+                    Some(movement) => self.play(movement),
+                    None => Step::CONTINUE,
+                }
+            } else if self
+                .clock
+                .as_ref()
+                .is_some_and(|clock| clock.is_flagged(self.state.turn))
+            {
+                Step::OVER(timeout_result(&self.state.board, self.state.turn))
+            } else if event::poll(Duration::from_millis(100))? {
+                // Polling instead of blocking keeps the clocks ticking on screen
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(key),
+                    Event::Mouse(mouse)
+                        if mouse.kind == MouseEventKind::Down(MouseButton::Left) =>
+                    {
+                        self.handle_click(mouse.column, mouse.row)
+                    }
+                    _ => Step::CONTINUE,
+                }
+            } else {
+                Step::IDLE
+            };
+            match step {
+                Step::CONTINUE => redraw = true,
+                Step::IDLE => {
+                    redraw = false;
+                    let mut out = stdout();
+                    self.draw_clocks(&mut out)?;
+                    out.flush()?;
+                }
+                Step::QUIT => return Ok(None),
+                Step::OVER(result) => return self.finish(result),
+            }
+        }
+    }
+
+    // Leaves the final position on screen until a key is pressed
+    fn finish(&mut self, result: GameResult) -> io::Result<Option<GameResult>> {
+        self.selected = None;
+        self.destinations.clear();
+        self.message = format!(
+            "{} ({}), tecle algo para sair",
+            result.announcement(),
+            result.score()
+        );
+        self.draw()?;
+        loop {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    return Ok(Some(result));
+                }
+            }
+        }
+    }
+}
+
+// Plays the game full screen, returning None when the player quits before it ends
+pub fn run_tui(
+    state: &mut GameState,
+    arguments: &Arguments,
+    view: &BoardView,
+    clock: &mut Option<Clock>,
+) -> io::Result<Option<GameResult>> {
+    let _terminal = RawTerminal::enter()?;
+    let first_move_number = state.fullmove_number;
+    let black_moved_first = state.turn == Color::BLACK;
+    let mut tui = Tui {
+        state,
+        arguments,
+        view,
+        clock,
+        cursor: (6, 4),
+        selected: None,
+        destinations: vec![],
+        promotion: None,
+        sans: vec![],
+        redo_stack: vec![],
+        first_move_number,
+        black_moved_first,
+        message: String::new(),
+    };
+    tui.run()
+}
@@ -0,0 +1,212 @@
+use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::engine::{self, SearchInfo, SearchLimits};
+use crate::fen::piece_to_letter;
+use crate::{
+    letter_to_promotion_piece, notation_to_position, Color, GameState, Movement, Piece,
+    DEFAULT_ENGINE_DEPTH,
+};
+
+// Long algebraic notation as spoken by UCI engines, e.g. "e2e4" or "e7e8q"
+pub fn movement_to_uci(movement: &Movement) -> String {
+    let mut uci = format!(
+        "{}{}",
+        movement.starting_position.to_notation(),
+        movement.destination_position.to_notation()
+    );
+    if let Some(piece_type) = movement.promotion {
+        uci.push(piece_to_letter(&Piece {
+            piece_type,
+            color: Color::BLACK,
+        }));
+    }
+    uci
+}
+
+pub fn uci_to_movement(uci: &str) -> Option<Movement> {
+    if !uci.is_ascii() || !(4..=5).contains(&uci.len()) {
+        return None;
+    }
+    let starting_position = notation_to_position(&uci[..2])?;
+    let destination_position = notation_to_position(&uci[2..4])?;
+    let promotion = match uci[4..].chars().next() {
+        Some(letter) => Some(letter_to_promotion_piece(letter)?),
+        None => None,
+    };
+    Some(Movement {
+        starting_position,
+        destination_position,
+        promotion,
+    })
+}
+
+// Always returns a position to search, so a later go never answers for a stale one: an invalid
+// FEN falls back to the starting position and the moves stop before the first illegal one
+fn parse_position(words: &[&str]) -> (GameState, Option<String>) {
+    let (mut state, moves) = match words {
+        ["startpos", rest @ ..] => (GameState::new(), rest),
+        ["fen", rest @ ..] => {
+            let fen_length = rest
+                .iter()
+                .position(|word| *word == "moves")
+                .unwrap_or(rest.len());
+            match GameState::from_fen(&rest[..fen_length].join(" ")) {
+                Ok(state) => (state, &rest[fen_length..]),
+                Err(err) => return (GameState::new(), Some(format!("invalid fen: {err}"))),
+            }
+        }
+        _ => {
+            return (
+                GameState::new(),
+                Some(String::from("expected 'startpos' or 'fen'")),
+            )
+        }
+    };
+
+    if let ["moves", moves @ ..] = moves {
+        for uci in moves {
+            let Some(movement) =
+                uci_to_movement(uci).filter(|movement| state.legal_moves().contains(movement))
+            else {
+                return (
+                    state,
+                    Some(format!("illegal move {uci}, ignoring the rest")),
+                );
+            };
+            state.make_move(movement);
+        }
+    }
+    (state, None)
+}
+
+fn parse_duration(value: Option<&&str>) -> Option<Duration> {
+    // GUIs may send negative times once a clock has run out
+    value
+        .and_then(|value| value.parse::<i64>().ok())
+        .map(|milliseconds| Duration::from_millis(milliseconds.max(0) as u64))
+}
+
+fn parse_go(words: &[&str], turn: Color) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    for (index, word) in words.iter().enumerate() {
+        let value = words.get(index + 1);
+        match *word {
+            "depth" => limits.depth = value.and_then(|value| value.parse().ok()),
+            "movetime" => limits.movetime = parse_duration(value),
+            "wtime" => limits.white_time = parse_duration(value),
+            "btime" => limits.black_time = parse_duration(value),
+            "winc" => limits.white_increment = parse_duration(value),
+            "binc" => limits.black_increment = parse_duration(value),
+            "movestogo" => limits.moves_to_go = value.and_then(|value| value.parse().ok()),
+            "infinite" => limits.infinite = true,
+            _ => continue,
+        }
+    }
+    // Without a depth or a time for the side to move the search would never send bestmove
+    if !limits.infinite && limits.depth.is_none() && limits.time_budget(turn).is_none() {
+        limits.depth = Some(DEFAULT_ENGINE_DEPTH);
+    }
+    limits
+}
+
+fn print_info(info: &SearchInfo) {
+    let score = if engine::is_mate_score(info.score) {
+        format!("mate {}", engine::mate_distance(info.score))
+    } else {
+        format!("cp {}", info.score)
+    };
+    println!(
+        "info depth {} score {} nodes {} time {} pv {}",
+        info.depth,
+        score,
+        info.nodes,
+        info.elapsed.as_millis(),
+        movement_to_uci(&info.best_move)
+    );
+}
+
+struct RunningSearch {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl RunningSearch {
+    fn start(state: &GameState, limits: SearchLimits) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut state = state.clone();
+        let thread_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let best = engine::search(&mut state, &limits, &thread_stop, &mut print_info);
+            // In infinite mode the GUI expects bestmove only after it sends stop
+            while limits.infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            match best {
+                Some(movement) => println!("bestmove {}", movement_to_uci(&movement)),
+                None => println!("bestmove 0000"),
+            }
+        });
+        RunningSearch { stop, handle }
+    }
+
+    fn finish(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().unwrap();
+    }
+}
+
+// Speaks the Universal Chess Interface over stdin/stdout until "quit" or the end of the input
+pub fn run_uci() {
+    let mut state = GameState::new();
+    let mut running_search: Option<RunningSearch> = None;
+
+    for line in stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["uci"] => {
+                println!("id name Terminal Chess");
+                println!("id author the Terminal Chess authors");
+                println!("uciok");
+            }
+            ["isready"] => println!("readyok"),
+            ["ucinewgame"] => {
+                if let Some(search) = running_search.take() {
+                    search.finish();
+                }
+                state = GameState::new();
+            }
+            ["position", rest @ ..] => {
+                let (new_state, err) = parse_position(rest);
+                state = new_state;
+                if let Some(err) = err {
+                    println!("info string {err}");
+                }
+            }
+            ["go", rest @ ..] => {
+                if let Some(search) = running_search.take() {
+                    search.finish();
+                }
+                running_search = Some(RunningSearch::start(&state, parse_go(rest, state.turn)));
+            }
+            ["stop"] => {
+                if let Some(search) = running_search.take() {
+                    search.finish();
+                }
+            }
+            ["quit"] => break,
+            _ => continue,
+        }
+        stdout().flush().unwrap();
+    }
+
+    if let Some(search) = running_search.take() {
+        search.finish();
+    }
+}
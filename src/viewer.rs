@@ -0,0 +1,98 @@
+use std::fs;
+use std::io::{stdin, stdout, Write};
+
+use crate::pgn::{self, PgnGame};
+use crate::render::BoardView;
+use crate::{show_board, Color, GameState};
+
+fn describe_ply(state: &GameState, game: &PgnGame, ply: usize) {
+    if ply == 0 {
+        println!("Posicao inicial");
+        return;
+    }
+    let pgn_move = &game.moves[ply - 1];
+    // The state already moved past this ply, so the mover is the opposite of the side to move
+    let move_number = if state.turn == Color::BLACK {
+        format!("{}.", state.fullmove_number)
+    } else {
+        format!("{}...", state.fullmove_number - 1)
+    };
+    let nags: String = pgn_move.nags.iter().map(|nag| format!(" ${nag}")).collect();
+    println!(
+        "Lance {ply}/{}: {move_number} {}{nags}",
+        game.moves.len(),
+        pgn_move.san
+    );
+    for comment in &pgn_move.comments {
+        println!("  {{{comment}}}");
+    }
+    if !pgn_move.variations.is_empty() {
+        println!("  ({} variante(s))", pgn_move.variations.len());
+    }
+}
+
+fn read_viewer_command() -> Option<String> {
+    print!("[n] proximo, [p] anterior, [f] primeiro, [l] ultimo, [q] sair: ");
+    stdout().flush().unwrap();
+
+    let mut command = String::new();
+    let read_bytes = stdin()
+        .read_line(&mut command)
+        .expect("Error reading the viewer command!");
+    if read_bytes == 0 {
+        return None;
+    }
+    Some(command.trim().to_string())
+}
+
+// Steps forward and back through the main line of a PGN file
+pub fn run_viewer(path: &str, view: &BoardView) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Could not read {path}: {err}"))?;
+    let game = pgn::parse_pgn(&text).map_err(|err| format!("Invalid PGN: {err}"))?;
+    let mut state = pgn::replay(&game).map_err(|err| format!("Invalid PGN: {err}"))?;
+
+    let movements: Vec<_> = state.history.iter().map(|record| record.movement).collect();
+    while state.unmake_move().is_some() {}
+
+    println!(
+        "{} vs {} ({})",
+        game.tag("White").unwrap_or("?"),
+        game.tag("Black").unwrap_or("?"),
+        game.result.as_deref().unwrap_or("*")
+    );
+
+    let mut ply = 0;
+    loop {
+        show_board(&state, view);
+        describe_ply(&state, &game, ply);
+
+        let Some(command) = read_viewer_command() else {
+            return Ok(());
+        };
+        match command.as_str() {
+            "" | "n" | "next" => {
+                if ply < movements.len() {
+                    state.make_move(movements[ply]);
+                    ply += 1;
+                }
+            }
+            "p" | "prev" | "previous" => {
+                if state.unmake_move().is_some() {
+                    ply -= 1;
+                }
+            }
+            "f" | "first" => {
+                while state.unmake_move().is_some() {}
+                ply = 0;
+            }
+            "l" | "last" => {
+                while ply < movements.len() {
+                    state.make_move(movements[ply]);
+                    ply += 1;
+                }
+            }
+            "q" | "quit" => return Ok(()),
+            _ => println!("Comando desconhecido '{command}'"),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::bitboard::square_index;
+use crate::{BoardSpot, CastlingRights, Color, GameState, Piece, PieceType, Position};
+
+struct Keys {
+    pieces: [[[u64; 64]; 6]; 2],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant: [u64; 8],
+}
+
+// SplitMix64, a fixed seed keeps the hashes the same from one run to the next
+const fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut mixed = *state;
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    mixed ^ (mixed >> 31)
+}
+
+const fn generate_keys() -> Keys {
+    let mut state = 0x5EED;
+    let mut keys = Keys {
+        pieces: [[[0; 64]; 6]; 2],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant: [0; 8],
+    };
+    let mut color = 0;
+    while color < 2 {
+        let mut piece_type = 0;
+        while piece_type < 6 {
+            let mut square = 0;
+            while square < 64 {
+                keys.pieces[color][piece_type][square] = next_random(&mut state);
+                square += 1;
+            }
+            piece_type += 1;
+        }
+        color += 1;
+    }
+    keys.black_to_move = next_random(&mut state);
+    let mut index = 0;
+    while index < 4 {
+        keys.castling[index] = next_random(&mut state);
+        index += 1;
+    }
+    index = 0;
+    while index < 8 {
+        keys.en_passant[index] = next_random(&mut state);
+        index += 1;
+    }
+    keys
+}
+
+const KEYS: Keys = generate_keys();
+
+pub fn piece_key(spot: BoardSpot, square: usize) -> u64 {
+    match spot {
+        BoardSpot::Piece(piece) => {
+            KEYS.pieces[piece.color as usize][piece.piece_type as usize][square]
+        }
+        BoardSpot::BLANK => 0,
+    }
+}
+
+fn castling_key(castling_rights: &CastlingRights) -> u64 {
+    [
+        castling_rights.white_kingside,
+        castling_rights.white_queenside,
+        castling_rights.black_kingside,
+        castling_rights.black_queenside,
+    ]
+    .iter()
+    .zip(KEYS.castling)
+    .filter(|(has_right, _)| **has_right)
+    .fold(0, |key, (_, right_key)| key ^ right_key)
+}
+
+// The en passant file only counts when a pawn of the side to move stands ready to capture,
+// otherwise positions differing by a double step that cannot be punished would never repeat
+fn en_passant_key(state: &GameState) -> u64 {
+    let Some(target) = state.en_passant else {
+        return 0;
+    };
+    let line = match state.turn {
+        Color::WHITE => target.line - 1,
+        Color::BLACK => target.line + 1,
+    };
+    let pawn = BoardSpot::Piece(Piece {
+        piece_type: PieceType::PAWN,
+        color: state.turn,
+    });
+    let can_capture = [target.column.wrapping_sub(1), target.column + 1]
+        .iter()
+        .any(|column| *column < 8 && state.board[line][*column] == pawn);
+    if can_capture {
+        KEYS.en_passant[target.column]
+    } else {
+        0
+    }
+}
+
+// Everything except piece placement, which make_move updates square by square
+pub fn state_key(state: &GameState) -> u64 {
+    let side_key = match state.turn {
+        Color::WHITE => 0,
+        Color::BLACK => KEYS.black_to_move,
+    };
+    side_key ^ castling_key(&state.castling_rights) ^ en_passant_key(state)
+}
+
+// Hashes the whole position from scratch, used when a state is created
+pub fn hash(state: &GameState) -> u64 {
+    let mut hash = state_key(state);
+    for (line, spots) in state.board.iter().enumerate() {
+        for (column, spot) in spots.iter().enumerate() {
+            hash ^= piece_key(*spot, square_index(&Position { line, column }));
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_incremental_hash(state: &mut GameState, depth: u32) {
+        assert_eq!(state.hash, hash(state), "{}", state.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for movement in state.legal_moves() {
+            let before = state.hash;
+            state.make_move(movement);
+            assert_incremental_hash(state, depth - 1);
+            state.unmake_move();
+            assert_eq!(state.hash, before);
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            assert_incremental_hash(&mut GameState::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn transpositions_share_a_hash() {
+        let mut knights = GameState::new();
+        let mut pawns = GameState::new();
+        for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+            let movement = knights.parse_san(san).unwrap();
+            knights.make_move(movement);
+        }
+        assert_eq!(knights.hash, GameState::new().hash);
+
+        // A double step nobody can capture en passant leaves no trace in the hash
+        for san in ["e4", "e5"] {
+            let movement = pawns.parse_san(san).unwrap();
+            pawns.make_move(movement);
+        }
+        let expected =
+            GameState::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
+                .unwrap();
+        assert_eq!(pawns.hash, expected.hash);
+        assert_ne!(pawns.hash, knights.hash);
+    }
+}